@@ -0,0 +1,30 @@
+// Lectura de la línea de comandos compartida por los modos sin ventana
+
+/// Busca el modo `flag` en `args`. `Ok(None)` si no se pidió; si no, su valor
+/// (cuando `value` dice qué falta, p. ej. "el archivo de salida") y el resto de las
+/// opciones en orden, sin el modo ni su valor.
+pub fn mode<'a>(args: &'a [String], flag: &str, value: Option<&str>)
+    -> Result<Option<(Option<&'a str>, impl Iterator<Item = &'a str>)>, String>
+{
+    let Some(i) = args.iter().position(|a| a == flag) else { return Ok(None); };
+    let v = match value {
+        None => None,
+        Some(what) => match args.get(i + 1) {
+            Some(a) if !a.starts_with("--") => Some(a.as_str()),
+            _ => return Err(format!("falta {what} después de {flag}")),
+        },
+    };
+    let skip = if v.is_some() { i + 1 } else { i };
+    let rest = args.iter().enumerate().filter(move |&(j, _)| j < i || j > skip).map(|(_, a)| a.as_str());
+    Ok(Some((v, rest)))
+}
+
+/// `--size ANCHOxALTO`: el valor que sigue en `rest`, los dos lados mayores que cero
+pub fn size<'a>(rest: &mut impl Iterator<Item = &'a str>) -> Result<(u32, u32), String> {
+    let v = rest.next().ok_or("--size necesita ANCHOxALTO")?;
+    let (w, h) = v.split_once('x').ok_or_else(|| format!("tamaño inválido: {v}"))?;
+    let w: u32 = w.parse().map_err(|_| format!("ancho inválido: {w}"))?;
+    let h: u32 = h.parse().map_err(|_| format!("alto inválido: {h}"))?;
+    if w == 0 || h == 0 { return Err(format!("tamaño inválido: {v}")); }
+    Ok((w, h))
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+
+use raylib::core::math::Vector3;
+
+use crate::args;
+use crate::raytracer::{render_mt, RenderMode, RenderOpts, SceneRT};
+use crate::world;
+
+pub const USAGE: &str =
+    "uso: Proyecto_2 --bench [--size 640x360] [--runs N] [--pt] [--world N]";
+
+/// Opciones del benchmark (DDA celda por celda vs. saltando el vacío)
+pub struct Options {
+    pub width: u32,
+    pub height: u32,
+    pub runs: u32,
+    pub path_traced: bool,
+    pub world: Option<usize>, // isla generada de NxN columnas en vez de las capas
+}
+
+impl Options {
+    /// `Ok(None)` si no se pidió `--bench`
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let Some((_, mut rest)) = args::mode(args, "--bench", None)? else { return Ok(None); };
+        let mut o = Options { width: 640, height: 360, runs: 5, path_traced: false, world: None };
+
+        while let Some(a) = rest.next() {
+            match a {
+                "--size" => (o.width, o.height) = args::size(&mut rest)?,
+                "--runs" => {
+                    let v = rest.next().ok_or("--runs necesita un número")?;
+                    o.runs = v.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("corridas inválidas: {v}"))?;
+                }
+                "--pt" => o.path_traced = true,
+                "--world" => {
+                    let v = rest.next().ok_or("--world necesita un tamaño")?;
+                    o.world = Some(v.parse().ok().filter(|&n| (16..=1024).contains(&n)).ok_or_else(|| format!("tamaño de mundo inválido: {v} (16..1024)"))?);
+                }
+                other => return Err(format!("opción desconocida: {other}")),
+            }
+        }
+        Ok(Some(o))
+    }
+}
+
+/// Mejor tiempo de `runs` renders (después de uno de calentamiento) y la imagen
+fn time_render(scene: &SceneRT, opts: &Options, sample: u32, ro: RenderOpts) -> (Duration, RgbaImage) {
+    let render = || render_mt(scene, opts.width, opts.height, sample, ro).expect("sin cancel el render no se corta").0;
+    let mut img = render();
+    let mut best = Duration::MAX;
+    for _ in 0..opts.runs {
+        let t0 = Instant::now();
+        img = render();
+        best = best.min(t0.elapsed());
+    }
+    (best, img)
+}
+
+/// Renderiza la escena inicial (o una isla generada) con `render_mt` recorriendo
+/// celda por celda y saltando el vacío; compara los tiempos y revisa que las
+/// imágenes salgan iguales
+pub fn run(mut scene: SceneRT, opts: &Options) -> Result<(), String> {
+    if let Some(n) = opts.world {
+        scene.set_blocks(world::generate_island(n, 1));
+        scene.xform = Default::default(); // la isla generada ya viene a su altura
+        scene.instances = Default::default();
+        scene.meshes = Default::default();
+        scene.shapes = Default::default();
+        // mirando la isla de lado y de cerca, para que haya vacío y terreno lejos
+        scene.cam.target = Vector3::new(0.0, 4.0, 0.0);
+        scene.cam.radius = n as f32 * 0.6;
+        scene.cam.pitch = 0.12;
+        println!("isla de {n}x{n}, {} bloques", scene.blocks.len());
+    }
+    println!("rejilla: {}", scene.grid.stats().label());
+    scene.cam.aspect = opts.width as f32 / opts.height as f32;
+    if opts.path_traced {
+        scene.render_mode = RenderMode::PathTraced { max_bounces: 4 };
+    }
+    println!("{}x{}, {}, mejor de {} corridas", opts.width, opts.height, scene.render_mode.label(), opts.runs);
+
+    // la muestra 0 va al centro del pixel; desde la 1 el rayo primario lleva jitter
+    let mut all_equal = true;
+    for (label, sample) in [("muestra 0", 0), ("muestra 1", 1)] {
+        let (tf, a) = time_render(&scene, opts, sample, RenderOpts { full_dda: true, ..Default::default() });
+        let (ts, b) = time_render(&scene, opts, sample, RenderOpts::default());
+        let diff = a.pixels().zip(b.pixels()).filter(|(p, q)| p != q).count();
+        all_equal &= diff == 0;
+        let ms = |t: Duration| t.as_secs_f64() * 1e3;
+        println!(
+            "{label}: celda por celda {:.1} ms, saltando vacío {:.1} ms ({:.2}x), {}",
+            ms(tf), ms(ts), tf.as_secs_f64() / ts.as_secs_f64().max(1e-9),
+            if diff == 0 { "imagen idéntica".to_string() } else { format!("{diff} pixeles distintos") },
+        );
+    }
+    // lo mismo sin niveles de detalle: la diferencia es lo que ahorra (y suaviza) trazar lo lejano en grueso
+    if scene.lod {
+        let (t_lod, lod_img) = time_render(&scene, opts, 0, RenderOpts::default());
+        let (t_fine, fine) = time_render(&SceneRT { lod: false, ..scene.clone() }, opts, 0, RenderOpts::default());
+        println!(
+            "niveles de detalle: con {:.1} ms, sin {:.1} ms ({:.2}x), {} pixeles cambian",
+            t_lod.as_secs_f64() * 1e3, t_fine.as_secs_f64() * 1e3,
+            t_fine.as_secs_f64() / t_lod.as_secs_f64().max(1e-9),
+            fine.pixels().zip(lod_img.pixels()).filter(|(p, q)| p != q).count(),
+        );
+    }
+    if all_equal { Ok(()) } else { Err("saltar el vacío no da la misma imagen que el DDA celda por celda".into()) }
+}
@@ -41,6 +41,20 @@ impl OrbitCamRT {
         }
     }
 
+    /// Flechas: mueve el punto que se mira en X/Z (adelante = hacia donde mira la cámara).
+    /// Devuelve si se movió.
+    pub fn pan_input(&mut self, rl: &RaylibHandle, dt: f32) -> bool {
+        use raylib::consts::KeyboardKey::*;
+        let axis = |neg, pos| (rl.is_key_down(pos) as i32 - rl.is_key_down(neg) as i32) as f32;
+        let (fwd, side) = (axis(KEY_DOWN, KEY_UP), axis(KEY_LEFT, KEY_RIGHT));
+        if fwd == 0.0 && side == 0.0 { return false; }
+        let speed = self.radius * dt; // más rápido de lejos
+        let (s, c) = self.yaw.sin_cos();
+        self.target.x += (-c * fwd + s * side) * speed;
+        self.target.z += (-s * fwd - c * side) * speed;
+        true
+    }
+
     pub fn eye(&self) -> Vector3 {
         let x = self.radius * self.pitch.cos() * self.yaw.cos();
         let y = self.radius * self.pitch.sin();
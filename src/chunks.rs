@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use raylib::core::math::Vector3;
+
+use crate::args;
+use crate::raytracer::{build_grid, Grid};
+use crate::world::{Block, BlockKind};
+
+/// Columnas por lado de cada chunk (todo el alto del mundo va en el mismo chunk)
+pub const CHUNK: i32 = 32;
+/// Radio de carga por defecto alrededor del punto que mira la cámara (en bloques)
+pub const DEFAULT_RADIUS: f32 = 96.0;
+/// Memoria por defecto para los chunks cargados (en MB)
+pub const DEFAULT_BUDGET_MB: usize = 64;
+
+const MAGIC: &[u8; 4] = b"VXC1";
+const INDEX: &str = "index.txt";
+/// Bytes por bloque en disco: x, z locales, y (u16) y tipo
+const RECORD: usize = 5;
+
+pub const USAGE: &str =
+    "uso: Proyecto_2 --write-chunks DIR [--world N]  |  Proyecto_2 --chunks DIR [--radius R] [--budget MB]";
+
+// ------------------- Escritura -------------------
+
+/// Chunk que contiene la columna del centro `c`
+fn chunk_of(c: Vector3) -> (i32, i32) {
+    ((c.x.floor() as i32).div_euclid(CHUNK), (c.z.floor() as i32).div_euclid(CHUNK))
+}
+
+fn chunk_path(dir: &Path, (cx, cz): (i32, i32)) -> PathBuf {
+    dir.join(format!("c_{cx}_{cz}.bin"))
+}
+
+/// Guarda `blocks` (solo superficie, como salen de `load_layers_dir`) partidos en
+/// chunks de CHUNKxCHUNK columnas: un archivo por chunk más `index.txt` con la
+/// altura de la capa 0. Devuelve cuántos chunks escribió.
+/// Las lámparas se guardan como tipo; al leerlas vuelven con la luz por defecto.
+pub fn write_chunks(dir: &Path, blocks: &[Block]) -> io::Result<usize> {
+    fs::create_dir_all(dir)?;
+    let y0 = blocks.iter().map(|b| b.center.y).fold(f32::INFINITY, f32::min);
+    let y0 = if y0.is_finite() { y0 } else { 0.5 };
+
+    let mut chunks: HashMap<(i32, i32), Vec<u8>> = HashMap::new();
+    for b in blocks {
+        let key = chunk_of(b.center);
+        let lx = b.center.x.floor() as i32 - key.0 * CHUNK;
+        let lz = b.center.z.floor() as i32 - key.1 * CHUNK;
+        let ly = (b.center.y - y0).round() as i32;
+        if !(0..=u16::MAX as i32).contains(&ly) {
+            eprintln!("WARN: bloque en y={} fuera del alto que cabe en un chunk, se omite", b.center.y);
+            continue;
+        }
+        let rec = chunks.entry(key).or_default();
+        rec.extend_from_slice(&[lx as u8, lz as u8]);
+        rec.extend_from_slice(&(ly as u16).to_le_bytes());
+        rec.push(kind_to_byte(b.kind));
+    }
+
+    for (&key, rec) in &chunks {
+        let mut data = Vec::with_capacity(8 + rec.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&((rec.len() / RECORD) as u32).to_le_bytes());
+        data.extend_from_slice(rec);
+        fs::write(chunk_path(dir, key), data)?;
+    }
+    fs::write(dir.join(INDEX), format!("# chunks de {CHUNK}x{CHUNK} columnas\nchunk {CHUNK}\ny0 {y0}\n"))?;
+    Ok(chunks.len())
+}
+
+fn kind_to_byte(k: BlockKind) -> u8 {
+    match k {
+        BlockKind::Grass => 0,
+        BlockKind::Dirt => 1,
+        BlockKind::Stone => 2,
+        BlockKind::Log => 3,
+        BlockKind::Leaves => 4,
+        BlockKind::Water => 5,
+        BlockKind::Lamp => 6,
+        BlockKind::Slab => 7,
+        BlockKind::Stairs => 8,
+        BlockKind::Fence => 9,
+        BlockKind::Pane => 10,
+        BlockKind::TallGrass => 11,
+        BlockKind::Flower => 12,
+        BlockKind::Sapling => 13,
+    }
+}
+
+fn byte_to_kind(b: u8) -> Option<BlockKind> {
+    Some(match b {
+        0 => BlockKind::Grass,
+        1 => BlockKind::Dirt,
+        2 => BlockKind::Stone,
+        3 => BlockKind::Log,
+        4 => BlockKind::Leaves,
+        5 => BlockKind::Water,
+        6 => BlockKind::Lamp,
+        7 => BlockKind::Slab,
+        8 => BlockKind::Stairs,
+        9 => BlockKind::Fence,
+        10 => BlockKind::Pane,
+        11 => BlockKind::TallGrass,
+        12 => BlockKind::Flower,
+        13 => BlockKind::Sapling,
+        _ => return None,
+    })
+}
+
+// ------------------- Lectura -------------------
+
+/// Altura de la capa 0 según `index.txt` (y revisa que el tamaño de chunk coincida)
+pub fn read_index(dir: &Path) -> Result<f32, String> {
+    let path = dir.join(INDEX);
+    let txt = fs::read_to_string(&path).map_err(|e| format!("No pude leer {}: {e}", path.display()))?;
+    let mut y0 = None;
+    for line in txt.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        match line.split_once(' ') {
+            Some(("chunk", v)) if v.trim().parse() == Ok(CHUNK) => {}
+            Some(("chunk", v)) => return Err(format!("{}: chunks de {v} columnas (se esperaban {CHUNK})", path.display())),
+            Some(("y0", v)) => y0 = v.trim().parse().ok(),
+            _ => eprintln!("WARN: {}: línea desconocida: {line}", path.display()),
+        }
+    }
+    y0.ok_or_else(|| format!("{}: falta y0", path.display()))
+}
+
+/// Bloques del chunk `key` (vacío si no hay archivo: ahí no hay nada)
+/// Cuántos bloques trae el chunk según el largo del archivo, sin leerlo (0 si no está)
+fn chunk_len(dir: &Path, key: (i32, i32)) -> usize {
+    fs::metadata(chunk_path(dir, key)).map_or(0, |m| (m.len() as usize).saturating_sub(8) / RECORD)
+}
+
+fn read_chunk(dir: &Path, y0: f32, key: (i32, i32)) -> Vec<Block> {
+    let path = chunk_path(dir, key);
+    let data = match fs::read(&path) {
+        Ok(d) => d,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => { eprintln!("WARN: No pude leer {}: {e}", path.display()); return Vec::new(); }
+    };
+    let n = match data.get(..8) {
+        Some(h) if &h[..4] == MAGIC => u32::from_le_bytes([h[4], h[5], h[6], h[7]]) as usize,
+        _ => { eprintln!("WARN: {} no es un chunk", path.display()); return Vec::new(); }
+    };
+    if data.len() != 8 + n * RECORD {
+        eprintln!("WARN: {} está cortado ({} bytes, se esperaban {})", path.display(), data.len(), 8 + n * RECORD);
+        return Vec::new();
+    }
+    let (ox, oz) = ((key.0 * CHUNK) as f32, (key.1 * CHUNK) as f32);
+    data[8..].chunks_exact(RECORD).filter_map(|r| {
+        let kind = byte_to_kind(r[4])?;
+        let y = u16::from_le_bytes([r[2], r[3]]) as f32;
+        Some(Block {
+            center: Vector3::new(ox + r[0] as f32 + 0.5, y0 + y, oz + r[1] as f32 + 0.5),
+            half: 0.5,
+            kind,
+            light: kind.light(),
+        })
+    }).collect()
+}
+
+// ------------------- Streaming -------------------
+
+/// Opciones del modo `--chunks`
+pub struct Options {
+    pub dir: PathBuf,
+    pub radius: f32,   // se cargan los chunks a menos de esto (en bloques) del punto que mira la cámara
+    pub budget: usize, // bytes para los chunks en memoria (los de la vista y la cache)
+}
+
+impl Options {
+    /// `Ok(None)` si no se pidió `--chunks`
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let Some((Some(dir), mut rest)) = args::mode(args, "--chunks", Some("la carpeta"))? else { return Ok(None); };
+        let mut o = Options { dir: PathBuf::from(dir), radius: DEFAULT_RADIUS, budget: DEFAULT_BUDGET_MB << 20 };
+
+        while let Some(a) = rest.next() {
+            match a {
+                "--radius" => {
+                    let v = rest.next().ok_or("--radius necesita un número")?;
+                    o.radius = v.parse().ok().filter(|r: &f32| *r > 0.0).ok_or_else(|| format!("radio inválido: {v}"))?;
+                }
+                "--budget" => {
+                    let v = rest.next().ok_or("--budget necesita los MB")?;
+                    o.budget = v.parse::<usize>().ok().filter(|&m| m > 0).ok_or_else(|| format!("presupuesto inválido: {v}"))? << 20;
+                }
+                other => return Err(format!("opción desconocida: {other}")),
+            }
+        }
+        Ok(Some(o))
+    }
+}
+
+/// `--write-chunks DIR [--world N]`: `Ok(None)` si no se pidió; si no, la carpeta y
+/// el tamaño de la isla generada (None = las capas de assets/layers)
+pub fn write_args(args: &[String]) -> Result<Option<(PathBuf, Option<usize>)>, String> {
+    let Some((Some(dir), mut rest)) = args::mode(args, "--write-chunks", Some("la carpeta"))? else { return Ok(None); };
+    let dir = PathBuf::from(dir);
+    let mut world = None;
+    while let Some(a) = rest.next() {
+        match a {
+            "--world" => {
+                let v = rest.next().ok_or("--world necesita un tamaño")?;
+                world = Some(v.parse().ok().filter(|&n| n >= 16).ok_or_else(|| format!("tamaño de mundo inválido: {v}"))?);
+            }
+            other => return Err(format!("opción desconocida: {other}")),
+        }
+    }
+    Ok(Some((dir, world)))
+}
+
+/// Lo que hay cargado alrededor de la cámara, listo para la escena
+pub struct Resident {
+    pub blocks: Arc<Vec<Block>>,
+    pub grid: Arc<Grid>,
+    pub stats: StreamStats,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamStats {
+    pub resident: usize, // chunks en la vista (cargados)
+    pub wanted: usize,   // chunks que tocan el radio de carga
+    pub cached: usize,   // chunks en memoria (los de la vista más los que quedaron de antes)
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+impl StreamStats {
+    /// Texto para el HUD
+    pub fn label(&self) -> String {
+        format!(
+            "Chunks: {}/{} in view, {} cached, {:.1}/{} MB",
+            self.resident, self.wanted, self.cached,
+            self.bytes as f64 / (1 << 20) as f64, self.budget >> 20,
+        )
+    }
+}
+
+/// Un chunk en memoria
+struct Cached {
+    blocks: Arc<Vec<Block>>,
+    bytes: usize,
+    used: u64, // última vez que estuvo en la vista (para sacar primero el más viejo)
+}
+
+struct Shared {
+    focus: Mutex<Option<Vector3>>, // el último punto pedido (uno nuevo pisa al anterior)
+    wake: Condvar,
+    quit: AtomicBool,
+    out: Mutex<Option<Resident>>,
+}
+
+/// Hilo que carga de disco los chunks alrededor de la cámara y suelta los que se
+/// alejan cuando la cache pasa del presupuesto. Cada vez que cambia el conjunto de
+/// chunks en la vista arma los bloques y la rejilla; la ventana los recoge con
+/// `latest`. Lo que no está cargado queda vacío para el trazado.
+pub struct ChunkStreamer {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ChunkStreamer {
+    pub fn spawn(opts: Options) -> Result<Self, String> {
+        let y0 = read_index(&opts.dir)?;
+        let shared = Arc::new(Shared {
+            focus: Mutex::new(None),
+            wake: Condvar::new(),
+            quit: AtomicBool::new(false),
+            out: Mutex::new(None),
+        });
+        let sh = Arc::clone(&shared);
+        let handle = thread::Builder::new()
+            .name("chunks".into())
+            .spawn(move || stream_loop(&sh, &opts, y0))
+            .map_err(|e| format!("No pude crear el hilo de chunks: {e}"))?;
+        Ok(Self { shared, handle: Some(handle) })
+    }
+
+    /// Pide los chunks alrededor de `p` (solo cuenta X/Z)
+    pub fn focus(&self, p: Vector3) {
+        *self.shared.focus.lock().unwrap() = Some(p);
+        self.shared.wake.notify_one();
+    }
+
+    /// Lo cargado más nuevo, si cambió desde la última vez que se pidió
+    pub fn latest(&self) -> Option<Resident> {
+        self.shared.out.lock().unwrap().take()
+    }
+
+    /// Espera a que termine la primera carga (para no arrancar con el mundo vacío)
+    pub fn wait_first(&self) -> Option<Resident> {
+        loop {
+            if let Some(r) = self.latest() { return Some(r); }
+            if self.handle.as_ref().is_none_or(|h| h.is_finished()) { return None; }
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+}
+
+impl Drop for ChunkStreamer {
+    fn drop(&mut self) {
+        self.shared.quit.store(true, Ordering::Relaxed);
+        {
+            // con el candado tomado, para que el aviso no se pierda entre revisar y esperar
+            let _focus = self.shared.focus.lock().unwrap();
+            self.shared.wake.notify_one();
+        }
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+/// Chunks que tocan el círculo de `radius` alrededor de `p`, el más cercano primero
+fn wanted_chunks(p: Vector3, radius: f32) -> Vec<(i32, i32)> {
+    let c = CHUNK as f32;
+    let dist2 = |(cx, cz): (i32, i32)| {
+        // distancia del punto al cuadrado del chunk
+        let dx = (cx as f32 * c - p.x).max(p.x - (cx + 1) as f32 * c).max(0.0);
+        let dz = (cz as f32 * c - p.z).max(p.z - (cz + 1) as f32 * c).max(0.0);
+        dx * dx + dz * dz
+    };
+    let lo = chunk_of(Vector3::new(p.x - radius, 0.0, p.z - radius));
+    let hi = chunk_of(Vector3::new(p.x + radius, 0.0, p.z + radius));
+    let mut v: Vec<((i32, i32), f32)> = (lo.1..=hi.1)
+        .flat_map(|cz| (lo.0..=hi.0).map(move |cx| (cx, cz)))
+        .map(|k| (k, dist2(k)))
+        .filter(|&(_, d2)| d2 <= radius * radius)
+        .collect();
+    v.sort_by(|a, b| a.1.total_cmp(&b.1));
+    v.into_iter().map(|(k, _)| k).collect()
+}
+
+fn stream_loop(sh: &Shared, opts: &Options, y0: f32) {
+    let mut cache: HashMap<(i32, i32), Cached> = HashMap::new();
+    let mut bytes = 0usize;      // los chunks en la cache
+    // bytes de rejilla por byte de bloques en la vista, de la última que se armó
+    // (para estimar la que viene antes de leer)
+    let mut grid_ratio = 0.0f32;
+    let mut tick = 0u64;
+    let mut last: Option<(i32, i32)> = None;   // chunk del último punto atendido
+    let mut published: Option<Vec<(i32, i32)>> = None;
+
+    loop {
+        let focus = {
+            let mut f = sh.focus.lock().unwrap();
+            loop {
+                if sh.quit.load(Ordering::Relaxed) { return; }
+                if let Some(p) = f.take() { break p; }
+                f = sh.wake.wait(f).unwrap();
+            }
+        };
+        // mientras no se cambie de chunk el conjunto de la vista es casi el mismo;
+        // basta con revisar cuando el punto cruza a otro chunk
+        let here = chunk_of(focus);
+        if last == Some(here) { continue; }
+        tick += 1;
+
+        let wanted = wanted_chunks(focus, opts.radius);
+        let mut resident = Vec::with_capacity(wanted.len());
+        // los bloques de la vista se copian juntos para publicarlos: también cuentan,
+        // y la rejilla que se arma con ellos se estima con la proporción de la última
+        let mut merged = 0usize;
+        for &key in &wanted {
+            let cached = cache.get_mut(&key).map(|c| { c.used = tick; c.blocks.len() });
+            // si no está, el tamaño sale del largo del archivo: se revisa antes de leerlo
+            let n = cached.unwrap_or_else(|| chunk_len(&opts.dir, key));
+            let size = if cached.is_some() { 0 } else { n * size_of::<Block>() + size_of::<Cached>() };
+            let need = |bytes: usize| {
+                let view = merged + n * size_of::<Block>();
+                bytes + view + (view as f32 * grid_ratio) as usize + size
+            };
+            // hacer lugar sacando los que no están en la vista, el más viejo primero
+            while need(bytes) > opts.budget {
+                let Some(old) = cache.iter().filter(|(_, c)| c.used < tick).min_by_key(|(_, c)| c.used).map(|(&k, _)| k) else { break; };
+                bytes -= cache.remove(&old).map_or(0, |c| c.bytes);
+            }
+            if need(bytes) > opts.budget {
+                // ni vaciando la cache alcanza: lo que falta de la vista (lo más lejos) queda vacío
+                break;
+            }
+            if cached.is_none() {
+                let blocks = read_chunk(&opts.dir, y0, key);
+                let size = blocks.len() * size_of::<Block>() + size_of::<Cached>();
+                bytes += size;
+                cache.insert(key, Cached { blocks: Arc::new(blocks), bytes: size, used: tick });
+            }
+            merged += cache[&key].blocks.len() * size_of::<Block>();
+            resident.push(key);
+        }
+        // si faltó alguno se vuelve a intentar con el próximo punto, aunque sea del mismo chunk
+        last = (resident.len() == wanted.len()).then_some(here);
+
+        let mut sorted = resident.clone();
+        sorted.sort_unstable();
+        if published.as_ref() == Some(&sorted) { continue; }
+        // la rejilla puede salir más grande que lo estimado: se quitan de la vista los
+        // chunks más lejanos (quedan en la cache) hasta que todo quepa
+        let (blocks, grid) = loop {
+            let mut blocks = Vec::with_capacity(merged / size_of::<Block>());
+            for k in &resident {
+                blocks.extend(cache[k].blocks.iter().cloned());
+            }
+            let grid = build_grid(&blocks);
+            let gs = grid.stats();
+            let grid_bytes = gs.bytes + gs.mip_bytes;
+            grid_ratio = grid_bytes as f32 / merged.max(1) as f32;
+            if bytes + merged + grid_bytes <= opts.budget || resident.is_empty() { break (blocks, grid); }
+            let far = resident.pop().unwrap();
+            merged -= cache[&far].blocks.len() * size_of::<Block>();
+            last = None;
+        };
+        let gs = grid.stats();
+        let stats = StreamStats {
+            resident: resident.len(), wanted: wanted.len(), cached: cache.len(),
+            bytes: bytes + merged + gs.bytes + gs.mip_bytes, budget: opts.budget,
+        };
+        *sh.out.lock().unwrap() = Some(Resident { blocks: Arc::new(blocks), grid: Arc::new(grid), stats });
+        resident.sort_unstable();
+        published = Some(resident);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Carpeta temporal propia de cada prueba (se borra al terminar)
+    struct TempDir(PathBuf);
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("vxc_{}_{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+    }
+
+    const KINDS: [BlockKind; 14] = [
+        BlockKind::Grass, BlockKind::Dirt, BlockKind::Stone, BlockKind::Log, BlockKind::Leaves,
+        BlockKind::Water, BlockKind::Lamp, BlockKind::Slab, BlockKind::Stairs, BlockKind::Fence,
+        BlockKind::Pane, BlockKind::TallGrass, BlockKind::Flower, BlockKind::Sapling,
+    ];
+
+    fn block(x: f32, y: f32, z: f32, kind: BlockKind) -> Block {
+        Block { center: Vector3::new(x, y, z), half: 0.5, kind, light: kind.light() }
+    }
+
+    /// (centro, tipo) ordenados, para comparar sin importar el orden de lectura
+    fn sorted(blocks: &[Block]) -> Vec<([i32; 3], u8)> {
+        let mut v: Vec<_> = blocks.iter()
+            .map(|b| ([b.center.x, b.center.y, b.center.z].map(|c| (c * 2.0) as i32), kind_to_byte(b.kind)))
+            .collect();
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn kind_bytes_round_trip() {
+        for k in KINDS { assert_eq!(byte_to_kind(kind_to_byte(k)), Some(k)); }
+        assert_eq!(byte_to_kind(KINDS.len() as u8), None);
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let tmp = TempDir::new("round_trip");
+        // de los dos lados del origen, en el borde de un chunk y a varias alturas
+        let mut blocks: Vec<Block> = KINDS.iter().enumerate()
+            .map(|(i, &k)| block(i as f32 - 7.5, -6.8 + i as f32, 3.5, k))
+            .collect();
+        blocks.push(block(-32.5, -6.8, -0.5, BlockKind::Stone));
+        blocks.push(block(31.5, 40.2, 31.5, BlockKind::Grass));
+        blocks.push(block(32.5, 40.2, 63.5, BlockKind::Water));
+
+        let written = write_chunks(&tmp.0, &blocks).unwrap();
+        let y0 = read_index(&tmp.0).unwrap();
+        assert_eq!(y0, -6.8);
+        let keys: Vec<(i32, i32)> = {
+            let mut k: Vec<_> = blocks.iter().map(|b| chunk_of(b.center)).collect();
+            k.sort_unstable();
+            k.dedup();
+            k
+        };
+        assert_eq!(written, keys.len());
+        let read: Vec<Block> = keys.iter().flat_map(|&k| read_chunk(&tmp.0, y0, k)).collect();
+        assert_eq!(sorted(&read), sorted(&blocks));
+        assert!(read.iter().all(|b| b.light.is_some() == (b.kind == BlockKind::Lamp)));
+        // donde no hay archivo no hay bloques
+        assert!(read_chunk(&tmp.0, y0, (100, 100)).is_empty());
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        let tmp = TempDir::new("broken");
+        write_chunks(&tmp.0, &[block(0.5, 0.5, 0.5, BlockKind::Dirt)]).unwrap();
+        // chunk cortado
+        let path = chunk_path(&tmp.0, (0, 0));
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(read_chunk(&tmp.0, 0.5, (0, 0)).is_empty());
+        // otro tamaño de chunk o sin y0
+        fs::write(tmp.0.join(INDEX), "chunk 16\ny0 0.5\n").unwrap();
+        assert!(read_index(&tmp.0).is_err());
+        fs::write(tmp.0.join(INDEX), format!("chunk {CHUNK}\n")).unwrap();
+        assert!(read_index(&tmp.0).is_err());
+    }
+}
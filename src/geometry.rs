@@ -14,6 +14,18 @@ pub struct Hit {
     pub uv: [f32; 2], // coord para textura
     pub id: i32,      // 0 piso, 1 cubo, -1 nada
     pub face: u8,     // 0:-X 1:+X 2:-Y 3:+Y 4:-Z 5:+Z
+    pub lod: u8,      // nivel de detalle (0 = bloque de 1)
+    pub inst: Option<u32>, // copia de modelo que se golpeó (None = el mundo)
+    pub surface: Surface,  // bloque, malla o forma
+}
+
+/// De qué es la superficie que se golpeó
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Surface {
+    #[default]
+    Block,     // cara de un bloque (textura según su tipo)
+    Mesh(u32), // triángulo de una malla, con ese material
+    Shape(u32), // forma suelta (esfera, toro...), con ese índice
 }
 
 impl Hit {
@@ -25,10 +37,73 @@ impl Hit {
             uv: [0.0, 0.0],
             id: -1,
             face: 255,
+            lod: 0,
+            inst: None,
+            surface: Surface::Block,
         }
     }
 }
 
+/// Dónde queda el mundo de bloques: traslación, giro alrededor de Y y escala pareja.
+/// Los bloques y la rejilla se quedan en sus celdas enteras; lo que se transforma son
+/// los rayos (a coordenadas de los bloques al trazar) y los impactos (de vuelta).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldXform {
+    pub offset: Vector3, // dónde cae el origen de los bloques
+    pub yaw: f32,        // giro en radianes (de +X hacia -Z)
+    pub scale: f32,      // lado de un bloque
+}
+
+impl Default for WorldXform {
+    fn default() -> Self {
+        Self { offset: Vector3::zero(), yaw: 0.0, scale: 1.0 }
+    }
+}
+
+impl WorldXform {
+    /// Girar `v` un ángulo con ese seno y coseno alrededor de Y
+    #[inline]
+    fn rotate(v: Vector3, (s, c): (f32, f32)) -> Vector3 {
+        Vector3::new(c * v.x + s * v.z, v.y, c * v.z - s * v.x)
+    }
+
+    /// Punto de los bloques -> punto de la escena
+    #[inline]
+    pub fn point_to_world(&self, p: Vector3) -> Vector3 {
+        self.offset + Self::rotate(p, self.yaw.sin_cos()) * self.scale
+    }
+
+    /// Punto de la escena -> punto de los bloques
+    #[inline]
+    pub fn point_to_local(&self, p: Vector3) -> Vector3 {
+        Self::rotate(p - self.offset, (-self.yaw).sin_cos()) / self.scale
+    }
+
+    /// Dirección de un rayo en los bloques. No se normaliza: así el `t` de un
+    /// impacto es el mismo en los dos sistemas.
+    #[inline]
+    pub fn ray_dir_to_local(&self, d: Vector3) -> Vector3 {
+        Self::rotate(d, (-self.yaw).sin_cos()) / self.scale
+    }
+
+    /// Normal de los bloques -> normal de la escena (la escala no la cambia)
+    #[inline]
+    pub fn normal_to_world(&self, n: Vector3) -> Vector3 {
+        Self::rotate(n, self.yaw.sin_cos())
+    }
+
+    /// Normal de la escena -> normal de los bloques
+    #[inline]
+    pub fn normal_to_local(&self, n: Vector3) -> Vector3 {
+        Self::rotate(n, (-self.yaw).sin_cos())
+    }
+
+    /// Poner primero `inner` y después `self` (p.ej. una copia de un modelo dentro del mundo)
+    pub fn compose(&self, inner: &WorldXform) -> WorldXform {
+        WorldXform { offset: self.point_to_world(inner.offset), yaw: self.yaw + inner.yaw, scale: self.scale * inner.scale }
+    }
+}
+
 // Intersección con el plano y = 0
 pub fn hit_plane_y0(ray: Ray) -> Option<Hit> {
     if ray.d.y.abs() < 1e-5 { return None; }
@@ -40,7 +115,10 @@ pub fn hit_plane_y0(ray: Ray) -> Option<Hit> {
         n: Vector3::new(0.0, 1.0, 0.0),
         uv: [p.x, p.z],
         id: 0,
-        face: 255
+        face: 255,
+        lod: 0,
+        inst: None,
+        surface: Surface::Block,
     })
 }
 
@@ -111,5 +189,24 @@ pub fn hit_aabb(ray: Ray, c: Vector3, he: f32) -> Option<Hit> {
         _ => [0.0, 0.0],
     };
 
-    Some(Hit { t, p, n, uv, id: 1, face })
+    Some(Hit { t, p, n, uv, id: 1, face, lod: 0, inst: None, surface: Surface::Block })
+}
+
+/// Intersección con el triángulo (p0, p0 + e1, p0 + e2) por los dos lados (Möller-Trumbore).
+/// Devuelve `t` y las coordenadas baricéntricas (u, v) de los vértices 1 y 2.
+#[inline]
+pub fn hit_triangle(ray: Ray, p0: Vector3, e1: Vector3, e2: Vector3) -> Option<(f32, f32, f32)> {
+    let pv = ray.d.cross(e2);
+    let det = e1.dot(pv);
+    if det.abs() < 1e-12 { return None; } // rayo paralelo al triángulo
+    let inv = 1.0 / det;
+    let tv = ray.o - p0;
+    let u = tv.dot(pv) * inv;
+    if !(0.0..=1.0).contains(&u) { return None; }
+    let qv = tv.cross(e1);
+    let v = ray.d.dot(qv) * inv;
+    if v < 0.0 || u + v > 1.0 { return None; }
+    let t = e2.dot(qv) * inv;
+    if t <= 1e-4 { return None; }
+    Some((t, u, v))
 }
@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+use crate::args;
+use crate::raytracer::{Progressive, RenderMode, SceneRT};
+
+pub const USAGE: &str =
+    "uso: Proyecto_2 --headless salida.png [--size 640x360] [--spp N] [--threshold E] [--night] [--pt] [--denoise]";
+
+/// Opciones del modo sin ventana (renderiza a un PNG y sale)
+pub struct Options {
+    pub out: String,
+    pub width: u32,
+    pub height: u32,
+    pub spp: Option<u32>,  // None = las que pida la escena para converger
+    pub threshold: f32,    // muestreo adaptativo (0 = todas las muestras a todos los pixeles)
+    pub night: bool,
+    pub path_traced: bool,
+    pub denoise: bool,
+}
+
+impl Options {
+    /// `Ok(None)` si no se pidió `--headless`
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let Some((Some(out), mut rest)) = args::mode(args, "--headless", Some("el archivo de salida"))? else { return Ok(None); };
+        let mut o = Options { out: out.to_string(), width: 640, height: 360, spp: None, threshold: 0.0, night: false, path_traced: false, denoise: false };
+
+        while let Some(a) = rest.next() {
+            match a {
+                "--size" => (o.width, o.height) = args::size(&mut rest)?,
+                "--spp" => {
+                    let v = rest.next().ok_or("--spp necesita un número")?;
+                    o.spp = Some(v.parse().map_err(|_| format!("muestras inválidas: {v}"))?);
+                }
+                "--threshold" => {
+                    let v = rest.next().ok_or("--threshold necesita un número")?;
+                    o.threshold = v.parse().ok().filter(|t: &f32| *t >= 0.0).ok_or_else(|| format!("umbral inválido: {v}"))?;
+                }
+                "--night" => o.night = true,
+                "--pt" => o.path_traced = true,
+                "--denoise" => o.denoise = true,
+                other => return Err(format!("opción desconocida: {other}")),
+            }
+        }
+        Ok(Some(o))
+    }
+}
+
+/// Acumula las muestras pedidas y guarda el resultado
+pub fn run(mut scene: SceneRT, opts: &Options) -> Result<(), String> {
+    scene.cam.aspect = opts.width as f32 / opts.height as f32;
+    scene.is_night = opts.night;
+    if opts.path_traced {
+        scene.render_mode = RenderMode::PathTraced { max_bounces: 4 };
+    }
+
+    scene.max_spp = opts.spp;
+    scene.noise_threshold = opts.threshold;
+    let mut progressive = Progressive::new(opts.width, opts.height);
+    progressive.denoise = opts.denoise;
+    progressive.temporal = false; // no hay cámara que se mueva
+
+    let t0 = Instant::now();
+    // con umbral se para antes si ya no queda ningún pixel con ruido
+    while !progressive.done(&scene) {
+        progressive.accumulate(&scene);
+    }
+    let (passes, mean) = (progressive.samples(), progressive.mean_spp());
+    let img = progressive.resolve();
+    img.save(&opts.out).map_err(|e| format!("No pude guardar {}: {e}", opts.out))?;
+    println!("{}: {}x{}, {} pasadas ({:.1} muestras por pixel) en {:.2?}", opts.out, opts.width, opts.height, passes, mean, t0.elapsed());
+    Ok(())
+}
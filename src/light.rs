@@ -1,7 +1,195 @@
 use raylib::prelude::*;
 use raylib::consts::KeyboardKey::*;
 
-/// Control simple de luz orbital alrededor de `target`
+// ----------- Tipos de luz de la escena -----------
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Sol: rayos paralelos; `dir` apunta HACIA la luz
+    Directional { dir: Vector3 },
+    Point { pos: Vector3 },
+    /// `dir` = hacia dónde alumbra; conos en radianes (medio ángulo)
+    Spot { pos: Vector3, dir: Vector3, inner: f32, outer: f32 },
+    /// Rectángulo centrado en `pos` con medios lados `u` y `v`; emite hacia `u × v`
+    Area { pos: Vector3, u: Vector3, v: Vector3 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vector3,  // lineal 0..1
+    pub intensity: f32,
+    /// Tamaño para sombras suaves: radio angular (rad) del sol, radio (m) de puntual/spot.
+    /// 0 = sombra dura. Las luces de área usan su rectángulo.
+    pub radius: f32,
+}
+
+/// Luz que llega a un punto desde una luz
+pub struct LightSample {
+    pub wi: Vector3,   // dirección hacia la luz
+    pub dist: f32,     // distancia para el rayo de sombra (INFINITY = sol)
+    pub le: Vector3,   // radiancia incidente (color * intensidad * caída)
+}
+
+impl Light {
+    /// Sol por defecto (mismo lugar que la luz puntual de antes)
+    pub fn sun(target: Vector3, pos: Vector3) -> Self {
+        Self {
+            kind: LightKind::Directional { dir: (pos - target).normalized() },
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            radius: 0.04,
+        }
+    }
+
+    /// Luz de tipo `kind_idx` (0..4) en `pos` apuntando a `target`, con intensidad típica
+    pub fn of_kind(kind_idx: usize, pos: Vector3, target: Vector3, color: Vector3) -> Self {
+        let (kind, intensity, radius) = match kind_idx % 4 {
+            0 => (LightKind::Directional { dir: Vector3::zero() }, 1.0, 0.04),
+            1 => (LightKind::Point { pos }, 30.0, 0.2),
+            2 => (LightKind::Spot { pos, dir: Vector3::zero(), inner: 20f32.to_radians(), outer: 30f32.to_radians() }, 40.0, 0.2),
+            _ => (LightKind::Area { pos, u: Vector3::new(0.75, 0.0, 0.0), v: Vector3::new(0.0, 0.0, 0.75) }, 15.0, 0.0),
+        };
+        let mut l = Self { kind, color, intensity, radius };
+        l.place(pos, target);
+        l
+    }
+
+    pub fn kind_index(&self) -> usize {
+        match self.kind {
+            LightKind::Directional { .. } => 0,
+            LightKind::Point { .. } => 1,
+            LightKind::Spot { .. } => 2,
+            LightKind::Area { .. } => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        ["Sun", "Point", "Spot", "Area"][self.kind_index()]
+    }
+
+    /// Posición "de agarre" para el rig (el sol se ve a `r` del target)
+    pub fn anchor(&self, target: Vector3, r: f32) -> Vector3 {
+        match self.kind {
+            LightKind::Directional { dir } => target + dir * r,
+            LightKind::Point { pos } | LightKind::Spot { pos, .. } | LightKind::Area { pos, .. } => pos,
+        }
+    }
+
+    /// Mueve la luz a `pos`; las que tienen orientación miran a `target`
+    pub fn place(&mut self, pos: Vector3, target: Vector3) {
+        let to_target = (target - pos).normalized();
+        match &mut self.kind {
+            LightKind::Directional { dir } => *dir = -to_target,
+            LightKind::Point { pos: p } => *p = pos,
+            LightKind::Spot { pos: p, dir, .. } => { *p = pos; *dir = to_target; }
+            LightKind::Area { pos: p, u, v } => {
+                // re-orientar el rectángulo de frente al target conservando tamaño
+                let (hu, hv) = (u.length(), v.length());
+                let helper = if to_target.y.abs() > 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+                let a = helper.cross(to_target).normalized();
+                let b = to_target.cross(a).normalized();
+                *p = pos;
+                *u = a * hu;
+                *v = b * hv; // u × v = to_target
+            }
+        }
+    }
+
+    /// ¿Da sombras suaves (hay que muestrearla varias veces)?
+    pub fn is_soft(&self) -> bool {
+        self.radius > 0.0 || matches!(self.kind, LightKind::Area { .. })
+    }
+
+    /// Ajusta el radio de sombra suave (paso según el tipo)
+    pub fn nudge_radius(&mut self, steps: f32) {
+        let (step, max) = match self.kind {
+            LightKind::Directional { .. } => (0.01, 0.3),
+            _ => (0.05, 2.0),
+        };
+        self.radius = (self.radius + step * steps).clamp(0.0, max);
+    }
+
+    /// Evalúa la luz que llega a `p` desde el punto `u` ∈ [0,1)² de la luz
+    /// (disco del sol/esfera, o el rectángulo del área). `[0.5, 0.5]` = centro.
+    pub fn sample(&self, p: Vector3, u: [f32; 2]) -> Option<LightSample> {
+        let base = self.color * self.intensity;
+        let (dx, dy) = concentric_disk(u);
+        match self.kind {
+            LightKind::Directional { dir } => {
+                let (t, b) = basis(dir);
+                let k = self.radius.tan();
+                let wi = (dir + t * (dx * k) + b * (dy * k)).normalized();
+                Some(LightSample { wi, dist: f32::INFINITY, le: base })
+            }
+            LightKind::Point { pos } => {
+                let (wi, dist) = dir_dist(p, self.jitter(p, pos, dx, dy))?;
+                Some(LightSample { wi, dist, le: base / (dist * dist) })
+            }
+            LightKind::Spot { pos, dir, inner, outer } => {
+                let (wi, dist) = dir_dist(p, self.jitter(p, pos, dx, dy))?;
+                let cone = smoothstep(outer.cos(), inner.cos(), (-wi).dot(dir));
+                if cone <= 0.0 { return None; }
+                Some(LightSample { wi, dist, le: base * (cone / (dist * dist)) })
+            }
+            LightKind::Area { pos, u: eu, v: ev } => {
+                let q = pos + eu * (2.0 * u[0] - 1.0) + ev * (2.0 * u[1] - 1.0);
+                let (wi, dist) = dir_dist(p, q)?;
+                let nl = eu.cross(ev);
+                let area = 4.0 * nl.length();
+                let cos_l = (-wi).dot(nl.normalized());
+                if cos_l <= 0.0 { return None; }
+                Some(LightSample { wi, dist, le: base * (area * cos_l / (dist * dist)) })
+            }
+        }
+    }
+}
+
+impl Light {
+    /// Punto en el disco de radio `radius` de la luz, de frente a `p`
+    #[inline]
+    fn jitter(&self, p: Vector3, pos: Vector3, dx: f32, dy: f32) -> Vector3 {
+        if self.radius <= 0.0 { return pos; }
+        let (t, b) = basis((pos - p).normalized());
+        pos + t * (dx * self.radius) + b * (dy * self.radius)
+    }
+}
+
+/// Cuadrado [0,1)² -> disco unitario (Shirley-Chiu, conserva estratos)
+#[inline]
+fn concentric_disk(u: [f32; 2]) -> (f32, f32) {
+    let (a, b) = (2.0 * u[0] - 1.0, 2.0 * u[1] - 1.0);
+    if a == 0.0 && b == 0.0 { return (0.0, 0.0); }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, std::f32::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, std::f32::consts::FRAC_PI_2 - std::f32::consts::FRAC_PI_4 * (a / b))
+    };
+    (r * phi.cos(), r * phi.sin())
+}
+
+/// Dos vectores perpendiculares a `n` (unitario)
+#[inline]
+pub fn basis(n: Vector3) -> (Vector3, Vector3) {
+    let helper = if n.y.abs() > 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+    let t = helper.cross(n).normalized();
+    (t, n.cross(t))
+}
+
+#[inline]
+fn dir_dist(p: Vector3, pos: Vector3) -> Option<(Vector3, f32)> {
+    let d = pos - p;
+    let dist = d.length();
+    if dist < 1e-4 { None } else { Some((d / dist, dist)) }
+}
+
+#[inline]
+fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Control simple de luz orbital alrededor de `target`.
+/// Mueve la luz seleccionada de `scene.lights` (N: siguiente, B: cambiar tipo, M: agregar).
 pub struct LightRig {
     pub yaw: f32,
     pub pitch: f32,
@@ -9,12 +197,53 @@ pub struct LightRig {
     pub target: Vector3,
     pub spin: bool,
     pub min_radius: f32, // radio mínimo para que la luz no entre al mundo
+    pub selected: usize,
 }
 
 impl LightRig {
     pub fn from_position(target: Vector3, pos: Vector3) -> Self {
         let (yaw, pitch, r) = cart_to_sph(pos, target);
-        Self { yaw, pitch, radius: r, target, spin: false, min_radius: 1.0 }
+        Self { yaw, pitch, radius: r, target, spin: false, min_radius: 1.0, selected: 0 }
+    }
+
+    /// Agarra la luz `idx` (el rig toma su posición actual)
+    pub fn select(&mut self, lights: &[Light], idx: usize) {
+        if lights.is_empty() { return; }
+        self.selected = idx % lights.len();
+        let pos = lights[self.selected].anchor(self.target, self.radius);
+        self.reset(pos);
+        self.radius = self.radius.max(self.min_radius);
+    }
+
+    /// Escribe la posición del rig en la luz seleccionada; true si cambió
+    pub fn apply(&self, lights: &mut [Light]) -> bool {
+        let Some(l) = lights.get_mut(self.selected) else { return false; };
+        let before = *l;
+        l.place(self.position(), self.target);
+        *l != before
+    }
+
+    /// N/B/M: seleccionar, cambiar tipo y agregar luces, [/]: radio; true si cambió algo
+    pub fn update_lights_input(&mut self, rl: &RaylibHandle, lights: &mut Vec<Light>) -> bool {
+        let mut changed = false;
+        if rl.is_key_pressed(KEY_N) {
+            self.select(lights, self.selected + 1);
+        }
+        if rl.is_key_pressed(KEY_B) && let Some(l) = lights.get_mut(self.selected) {
+            *l = Light::of_kind(l.kind_index() + 1, self.position(), self.target, l.color);
+            changed = true;
+        }
+        // [ / ]: radio de sombra suave de la luz escogida
+        if let Some(l) = lights.get_mut(self.selected) {
+            if rl.is_key_pressed(KEY_LEFT_BRACKET)  { l.nudge_radius(-1.0); changed = true; }
+            if rl.is_key_pressed(KEY_RIGHT_BRACKET) { l.nudge_radius(1.0); changed = true; }
+        }
+        if rl.is_key_pressed(KEY_M) {
+            lights.push(Light::of_kind(1, self.position(), self.target, Vector3::new(1.0, 0.9, 0.8)));
+            self.selected = lights.len() - 1;
+            changed = true;
+        }
+        changed
     }
 
     pub fn position(&self) -> Vector3 {
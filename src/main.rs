@@ -1,14 +1,21 @@
+mod args;
 mod camera;
 mod geometry;
 mod raytracer; // carpeta src/raytracer/ con mod.rs, etc.
 mod world;
 mod light;
 mod hud;
+mod headless;
+mod bench;
+mod chunks;
+mod obj;
+
+use std::sync::Arc;
 
 use raylib::prelude::*;
 use camera::OrbitCamRT;
-use raytracer::{SceneRT, WaterMode};
-use light::LightRig;
+use raytracer::{build_grid, AoMode, Instances, Meshes, Shape, Shapes, Change, SceneRT, WaterMode, LampSampling, Progressive, RenderMode, RenderSettings, RenderWorker, SamplerKind};
+use light::{Light, LightRig};
 
 fn nearly(a: f32, b: f32, eps: f32) -> bool { (a - b).abs() <= eps }
 fn v_eq(a: Vector3, b: Vector3, eps: f32) -> bool {
@@ -22,17 +29,31 @@ fn water_mode_eq(a: WaterMode, b: WaterMode) -> bool {
     )
 }
 
-fn main() {
-    let (mut rl, thread) = raylib::init()
-        .size(1280, 720)
-        .title("Proyecto2 Gráficas — CPU Ray Tracing")
-        .msaa_4x()
-        .build();
-    rl.set_target_fps(60);
+// sol inicial
+const LIGHT_TARGET: Vector3 = Vector3::new(0.0, 0.5, 0.0);
+const SUN_POS: Vector3 = Vector3::new(3.0, 4.0, 2.0);
+/// Tope para subir las muestras con la tecla =
+const MAX_SPP_LIMIT: u32 = 4096;
+/// Mover el mundo con las teclas: Z/X baja/sube (unidades por segundo), Q/E gira
+/// (radianes por segundo), ,/. escala (factor e^v por segundo, entre los topes)
+const WORLD_MOVE_SPEED: f32 = 1.0;
+const WORLD_TURN_SPEED: f32 = 0.8;
+const WORLD_GROW_SPEED: f32 = 0.5;
+const WORLD_SCALE_RANGE: (f32, f32) = (0.5, 4.0);
+/// Radio (en la escena) de las esferas que marcan dónde están las luces (tecla Y)
+const LIGHT_MARKER_RADIUS: f32 = 0.12;
 
-    // -------- resolución del render --------
-    let mut half_res = true;  // empezar en resolución baja para mejor rendimiento
+/// Una esfera que brilla con el color de cada luz, en las coordenadas de los bloques
+fn light_markers(scene: &SceneRT, rig: &LightRig) -> Vec<Shape> {
+    let xf = &scene.xform;
+    scene.lights.iter().map(|l| {
+        let p = xf.point_to_local(l.anchor(rig.target, rig.radius));
+        Shape::marker(p, LIGHT_MARKER_RADIUS / xf.scale, l.color)
+    }).collect()
+}
 
+/// Escena inicial (materiales, capas, lámparas, sol); la usan la ventana y el modo headless
+fn build_scene() -> SceneRT {
     // -------- materiales --------
     let mats = world::Materials {
         grass_top:  image::open("assets/grasstop.png").expect("Falta assets/grasstop.png").to_rgba8(),
@@ -45,44 +66,179 @@ fn main() {
         water:      image::open("assets/water.png").expect("Falta assets/water.png").to_rgba8(),
         lamp_off:   image::open("assets/lamp_off.png").expect("Falta assets/lamp_off.png").to_rgba8(),
         lamp_on:    image::open("assets/lamp_on.png").expect("Falta assets/lamp_on.png").to_rgba8(),
+        pane:       image::open("assets/pane.png").expect("Falta assets/pane.png").to_rgba8(),
+        tall_grass: image::open("assets/tall_grass.png").expect("Falta assets/tall_grass.png").to_rgba8(),
+        flower:     image::open("assets/flower.png").expect("Falta assets/flower.png").to_rgba8(),
+        sapling:    image::open("assets/sapling.png").expect("Falta assets/sapling.png").to_rgba8(),
+        averages:   Default::default(),
     };
 
     // -------- capas -> bloques --------
-    let mut blocks = world::load_layers_dir("assets/layers", "layer_", 16, 16);
+    // las capas pueden ser más grandes que 16x16 (mapas importados)
+    let (lw, lh) = world::layers_size("assets/layers", "layer_");
+    let mut blocks = world::load_layers_dir("assets/layers", "layer_", lw, lh);
     if blocks.is_empty() {
         blocks.push(world::Block {
             center: Vector3::new(0.0, 0.5, 0.0),
             half: 0.5,
             kind: world::BlockKind::Grass,
+            light: None,
         });
-        eprintln!("TIP: crea assets/layers/layer_00.txt (16x16) y sucesivos layer_01.txt, ...");
+        eprintln!("TIP: crea assets/layers/layer_00.txt (16x16 o más) y sucesivos layer_01.txt, ...");
     }
 
-    // acomodar isla (techo ≈ 1.2)
-    {
-        let mut maxy = -f32::INFINITY;
-        for b in &blocks { maxy = maxy.max(b.center.y); }
-        let target_top_y: f32 = 1.2;
-        let dy = target_top_y - maxy;
-        world::translate_blocks_y(&mut blocks, dy);
-    }
+    // luces por lámpara (color/intensidad/rango/parpadeo), opcional
+    world::load_lamp_overrides("assets/lamps.txt", lw, lh, &mut blocks);
+
+    // copias de modelos de assets/models/ (opcional), en coordenadas de capa como las lámparas:
+    // carpetas de capas o mallas .obj
+    let (objs, models): (Vec<_>, Vec<_>) = world::load_placements("assets/instances.txt", lw, lh)
+        .into_iter().partition(|p| p.model.ends_with(".obj"));
+    let instances = Instances::load("assets/models", &models);
+    let meshes = Meshes::load("assets/models", &objs);
+    // esferas, toros... sueltos (opcional), también en coordenadas de capa
+    let shapes = Shapes::load("assets/shapes.txt", lw, lh);
 
     // -------- escena --------
-    let cam = OrbitCamRT::new(Vector3::new(0.0, 0.5, 0.0), 1280.0/720.0);
-    let mut scene = SceneRT {
+    let mut cam = OrbitCamRT::new(Vector3::new(0.0, 0.5, 0.0), 1280.0/720.0);
+    cam.radius *= (lw.max(lh) as f32 / 16.0).max(1.0); // que un mapa grande quepa en la vista
+    let grid = Arc::new(build_grid(&blocks));
+    let xform = world::home_xform(&blocks); // acomodar isla (techo ≈ 1.2)
+    SceneRT {
         cam,
-        light_pos: Vector3::new(3.0, 4.0, 2.0),
+        lights: vec![Light::sun(LIGHT_TARGET, SUN_POS)],
         floor_color: Vector3::new(0.06, 0.07, 0.08),
         show_floor: false,
-        blocks,
-        mats,
+        blocks: Arc::new(blocks),
+        grid,
+        xform,
+        instances: Arc::new(instances),
+        meshes: Arc::new(meshes),
+        shapes: Arc::new(shapes),
+        mats: Arc::new(mats),
         water_mode: WaterMode::Off,  // cambiar a Off para mejor rendimiento inicial
         is_night: false,  // empezar en modo día
+        lamp_sampling: LampSampling::All,
+        time: 0.0,
+        shadow_samples: 4,
+        ao: AoMode::Voxel,
+        render_mode: RenderMode::Whitted,
+        sampler: SamplerKind::Sobol,
+        max_spp: None,
+        noise_threshold: 0.0,
+        lod: true,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // --write-chunks DIR: parte el mundo (capas o isla generada) en chunks en disco y sale
+    match chunks::write_args(&args) {
+        Ok(None) => {}
+        Ok(Some((dir, world))) => {
+            let blocks = match world {
+                Some(n) => world::generate_island(n, 1),
+                None => Arc::unwrap_or_clone(build_scene().blocks),
+            };
+            match chunks::write_chunks(&dir, &blocks) {
+                Ok(n) => println!("{}: {n} chunks de {c}x{c} columnas, {} bloques", dir.display(), blocks.len(), c = chunks::CHUNK),
+                Err(e) => {
+                    eprintln!("ERROR: No pude escribir los chunks en {}: {e}", dir.display());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("ERROR: {e}\n{}", chunks::USAGE);
+            std::process::exit(2);
+        }
+    }
+    // --bench: compara el DDA celda por celda con el que salta el vacío y sale
+    match bench::Options::from_args(&args) {
+        Ok(None) => {}
+        Ok(Some(opts)) => {
+            if let Err(e) = bench::run(build_scene(), &opts) {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("ERROR: {e}\n{}", bench::USAGE);
+            std::process::exit(2);
+        }
+    }
+    // --headless salida.png: renderiza sin ventana y sale
+    match headless::Options::from_args(&args) {
+        Ok(None) => {}
+        Ok(Some(opts)) => {
+            if let Err(e) = headless::run(build_scene(), &opts) {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("ERROR: {e}\n{}", headless::USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    // --chunks DIR: el mundo se va cargando de disco alrededor de la cámara
+    let stream_opts = match chunks::Options::from_args(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("ERROR: {e}\n{}", chunks::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let (mut rl, thread) = raylib::init()
+        .size(1280, 720)
+        .title("Proyecto2 Gráficas — CPU Ray Tracing")
+        .msaa_4x()
+        .build();
+    rl.set_target_fps(60);
+
+    // -------- resolución del render --------
+    let mut half_res = true;  // empezar en resolución baja para mejor rendimiento
+
+    let mut scene = build_scene();
+    let mut stream_stats = None;
+    let streamer = match stream_opts.map(chunks::ChunkStreamer::spawn).transpose() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
     };
+    if let Some(st) = &streamer {
+        st.focus(scene.xform.point_to_local(scene.cam.target));
+        if let Some(r) = st.wait_first() {
+            (scene.blocks, scene.grid, stream_stats) = (r.blocks, r.grid, Some(r.stats));
+            scene.xform = world::home_xform(&scene.blocks);
+        }
+    }
+    let home_xform = scene.xform; // a donde vuelve la C
+    let animated_lamps = world::has_animated_lamps(&scene.blocks)
+        || scene.instances.models.iter().any(|m| world::has_animated_lamps(&m.blocks));
+    println!("Mundo: {}", scene.grid.stats().label());
+    if !scene.instances.is_empty() {
+        println!("Modelos: {}", scene.instances.label());
+    }
+    if !scene.meshes.is_empty() {
+        println!("Mallas: {}", scene.meshes.label());
+    }
+    if !scene.shapes.is_empty() {
+        println!("Formas: {}", scene.shapes.len());
+    }
+    let base_shapes = scene.shapes.clone(); // sin las marcas de las luces
+    let mut show_markers = false;
 
     // -------- LUZ + HUD --------
-    let mut light_rig = LightRig::from_position(Vector3::new(0.0, 0.5, 0.0), scene.light_pos);
-    light_rig.min_radius = world::suggest_min_light_radius(16, 16, &scene.blocks);
+    let mut light_rig = LightRig::from_position(LIGHT_TARGET, SUN_POS);
+    light_rig.min_radius = world::suggest_min_light_radius(scene.grid.w as usize, scene.grid.d as usize, &scene.blocks, &scene.xform);
 
     let mut hud = hud::Hud::new();
 
@@ -91,61 +247,79 @@ fn main() {
     let mut rimg = Image::gen_image_color(tex_w, tex_h, Color::BLACK);
     let mut rtex = rl.load_texture_from_image(&thread, &rimg).unwrap();
 
-    // cache "dirty" para CPU + frame skipping para optimización
+    // render en otro hilo: acumula muestras (sombras suaves, lámparas estocásticas)
+    // mientras la ventana sigue a 60 fps
+    let mut worker = RenderWorker::spawn();
+    let mut settings = RenderSettings::default();
+
+    // cache "dirty" para CPU
     let mut last_eye    = scene.cam.eye();
     let mut last_target = scene.cam.target;
-    let mut last_light  = scene.light_pos;
     let mut last_mode   = scene.water_mode;
     let mut last_is_night = scene.is_night;  // nuevo cache para día/noche
+    let mut last_lamp_sampling = scene.lamp_sampling;
+    let mut last_shadow_samples = scene.shadow_samples;
+    let mut last_ao = scene.ao;
+    let mut last_render_mode = scene.render_mode;
+    let mut last_sampler = scene.sampler;
+    let mut last_spp = (scene.max_spp, scene.noise_threshold);
+    let mut last_lod = scene.lod;
+    let mut last_markers = show_markers;
+    let mut last_settings = settings;
     let mut last_wh     = (tex_w, tex_h);
     let mut rtex_has_image = false;
-    let mut frame_skip_counter = 0u32;  // contador para skipping de frames
+    let mut submitted = false;
 
     while !rl.window_should_close() {
         // ===== INPUT =====
-        let (moved_blocks, _moved_light) = {
+        let (moved_blocks, moved_light) = {
             let mut moved_blocks = false;
-            let mut moved_light = false;
 
             // control de cámara
             scene.cam.apply_input(&rl);
 
-            // control de luz (optimizado)
+            // control de luz: N/B/M eligen/cambian/agregan, J/L/I/K/U/O mueven la seleccionada
             let dt = rl.get_frame_time();
-            let prev_light = light_rig.position();
+            scene.time += dt;
+            let edited = light_rig.update_lights_input(&rl, &mut scene.lights);
             light_rig.update_input(&rl, dt);
-            let new_light = light_rig.position();
-            moved_light = !v_eq(prev_light, new_light, 0.01);  // epsilon más grande
-            if moved_light {
-                scene.light_pos = new_light;
-            }
+            let moved_light = light_rig.apply(&mut scene.lights) || edited;
 
             // control del HUD
             hud.update_input(&rl);
 
-            // subir/bajar isla Z/X
-            if rl.is_key_pressed(KeyboardKey::KEY_Z) {
-                world::translate_blocks_y(&mut scene.blocks, -0.1);
-                moved_blocks = true;
-            }
-            if rl.is_key_pressed(KeyboardKey::KEY_X) {
-                world::translate_blocks_y(&mut scene.blocks, 0.1);
-                moved_blocks = true;
-            }
+            // mover el mundo: Z/X lo bajan/suben, Q/E lo giran, ,/. lo achican/agrandan y C
+            // lo devuelve a su lugar. Solo cambia `scene.xform` (se aplica a los rayos):
+            // los bloques y la rejilla no se tocan, así que puede quedar entre celdas
+            let axis = |neg, pos| (rl.is_key_down(pos) as i32 - rl.is_key_down(neg) as i32) as f32;
+            let before = scene.xform;
+            scene.xform.offset.y += axis(KeyboardKey::KEY_Z, KeyboardKey::KEY_X) * WORLD_MOVE_SPEED * dt;
+            scene.xform.yaw += axis(KeyboardKey::KEY_Q, KeyboardKey::KEY_E) * WORLD_TURN_SPEED * dt;
+            let grow = (axis(KeyboardKey::KEY_COMMA, KeyboardKey::KEY_PERIOD) * WORLD_GROW_SPEED * dt).exp();
+            scene.xform.scale = (scene.xform.scale * grow).clamp(WORLD_SCALE_RANGE.0, WORLD_SCALE_RANGE.1);
             if rl.is_key_pressed(KeyboardKey::KEY_C) {
-                // reset height (acomodar techo ≈ 1.2)
-                let mut maxy = -f32::INFINITY;
-                for b in &scene.blocks { maxy = maxy.max(b.center.y); }
-                let dy = 1.2 - maxy;
-                world::translate_blocks_y(&mut scene.blocks, dy);
-                moved_blocks = true;
+                scene.xform = home_xform;
+            }
+            let moved_world = scene.xform != before;
+            moved_blocks |= moved_world;
+
+            // con chunks: flechas mueven el punto que mira la cámara y se piden los chunks de ahí
+            // (en las coordenadas de los bloques); cuando llegan se cambia el mundo entero
+            if let Some(st) = &streamer {
+                if scene.cam.pan_input(&rl, dt) || moved_world {
+                    st.focus(scene.xform.point_to_local(scene.cam.target));
+                }
+                if let Some(r) = st.latest() {
+                    (scene.blocks, scene.grid, stream_stats) = (r.blocks, r.grid, Some(r.stats));
+                    moved_blocks = true;
+                }
             }
 
             (moved_blocks, moved_light)
         };
         
         if moved_blocks {
-            light_rig.min_radius = world::suggest_min_light_radius(16,16,&scene.blocks);
+            light_rig.min_radius = world::suggest_min_light_radius(scene.grid.w as usize, scene.grid.d as usize, &scene.blocks, &scene.xform);
         }
 
         // toggles
@@ -166,6 +340,80 @@ fn main() {
         if rl.is_key_pressed(KeyboardKey::KEY_F5) {
             scene.is_night = !scene.is_night;
         }
+        // F7: todas las lámparas vs. muestreo estocástico (para escenas con cientos)
+        if rl.is_key_pressed(KeyboardKey::KEY_F7) {
+            scene.lamp_sampling = match scene.lamp_sampling {
+                LampSampling::All => LampSampling::Stochastic(4),
+                LampSampling::Stochastic(_) => LampSampling::All,
+            };
+        }
+        // F8: rayos de sombra por luz suave (1 → 4 → 16)
+        if rl.is_key_pressed(KeyboardKey::KEY_F8) {
+            scene.shadow_samples = match scene.shadow_samples { 1 => 4, 4 => 16, _ => 1 };
+        }
+        // F9: oclusión ambiental (off → voxel → trazada)
+        if rl.is_key_pressed(KeyboardKey::KEY_F9) {
+            scene.ao = match scene.ao {
+                AoMode::Off => AoMode::Voxel,
+                AoMode::Voxel => AoMode::RayTraced { radius: 1.5, samples: 4 },
+                AoMode::RayTraced { .. } => AoMode::Off,
+            };
+        }
+        // F10: Whitted → path tracing → solo AO → vistas de depuración
+        if rl.is_key_pressed(KeyboardKey::KEY_F10) {
+            scene.render_mode = scene.render_mode.next();
+        }
+        // F11: secuencia de muestreo (independiente, estratificada, Halton, Sobol, ruido azul)
+        if rl.is_key_pressed(KeyboardKey::KEY_F11) {
+            scene.sampler = scene.sampler.next();
+        }
+        // V: muestreo adaptativo (umbral de ruido); -/=: bajar/subir el tope de muestras
+        if rl.is_key_pressed(KeyboardKey::KEY_V) {
+            scene.noise_threshold = match scene.noise_threshold {
+                t if t <= 0.0 => 0.05,
+                t if t > 0.03 => 0.02,
+                t if t > 0.015 => 0.01,
+                _ => 0.0,
+            };
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_MINUS) {
+            scene.max_spp = Some((Progressive::target_samples(&scene) / 2).max(1));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) {
+            scene.max_spp = Some((Progressive::target_samples(&scene) * 2).min(MAX_SPP_LIMIT));
+        }
+        // G: niveles de detalle para lo lejano
+        if rl.is_key_pressed(KeyboardKey::KEY_G) {
+            scene.lod = !scene.lod;
+        }
+        // Y: marcar dónde están las luces con esferas que brillan
+        if rl.is_key_pressed(KeyboardKey::KEY_Y) {
+            show_markers = !show_markers;
+        }
+        // las marcas siguen a las luces y, como van en los bloques, también al mundo
+        let markers_changed = last_markers != show_markers;
+        if markers_changed || (show_markers && (moved_light || moved_blocks)) {
+            scene.shapes = if show_markers {
+                Arc::new(base_shapes.with(light_markers(&scene, &light_rig)))
+            } else {
+                base_shapes.clone()
+            };
+        }
+        // F2: denoiser guiado por normal/profundidad/albedo
+        if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            settings.denoise = !settings.denoise;
+        }
+        // F3: reusar el frame anterior mientras se mueve la cámara
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            settings.temporal = !settings.temporal;
+        }
+        // F4: resolución dinámica (baja la resolución al interactuar para no pasarse de 33 ms)
+        if rl.is_key_pressed(KeyboardKey::KEY_F4) {
+            settings.frame_budget = match settings.frame_budget {
+                Some(_) => None,
+                None => RenderSettings::default().frame_budget,
+            };
+        }
 
         // mantener aspect
         let sw_i: i32 = rl.get_screen_width();
@@ -186,22 +434,31 @@ fn main() {
         let tgt = scene.cam.target;
         let eps = 0.001;  // epsilon más grande para menos renders innecesarios
         let cam_changed   = !v_eq(eye, last_eye, eps) || !v_eq(tgt, last_target, eps);
-        let light_changed = !v_eq(scene.light_pos, last_light, eps);
+        let light_changed = moved_light;
         let wh_changed    = last_wh != (tex_w, tex_h);
         let mode_changed  = !water_mode_eq(last_mode, scene.water_mode);
         let night_changed = last_is_night != scene.is_night;  // detectar cambio día/noche
-        let dirty = cam_changed || light_changed || moved_blocks || wh_changed || mode_changed || night_changed || !rtex_has_image;
-        
-        // Frame skipping para mejor rendimiento - renderizar solo cada pocos frames si no hay cambios grandes
-        frame_skip_counter += 1;
-        let should_render = dirty || (frame_skip_counter % 3 == 0);  // renderizar cada 3 frames si no hay cambios
-        
-        if should_render {
-            let img = raytracer::render_mt(&scene, tex_w as u32, tex_h as u32);
+        let lamps_changed = last_lamp_sampling != scene.lamp_sampling
+            || (scene.is_night && animated_lamps);  // parpadeo: cada frame cambia
+        let shadows_changed = last_shadow_samples != scene.shadow_samples || last_ao != scene.ao
+            || last_render_mode != scene.render_mode || last_sampler != scene.sampler
+            || last_spp != (scene.max_spp, scene.noise_threshold) || last_settings != settings
+            || last_lod != scene.lod || markers_changed;
+        let scene_changed = light_changed || moved_blocks || wh_changed || mode_changed || night_changed || lamps_changed || shadows_changed || !submitted;
+
+        // Si algo cambió el hilo de render tira lo acumulado; si solo se movió la cámara
+        // reproyecta el frame anterior; si no, sigue sumando muestras solo hasta converger
+        if scene_changed || cam_changed {
+            let change = if scene_changed { Change::Scene } else { Change::Camera };
+            worker.submit(&scene, tex_w as u32, tex_h as u32, change, settings);
+            submitted = true;
+            last_eye = eye; last_target = tgt; last_wh = (tex_w, tex_h); last_mode = scene.water_mode; last_is_night = scene.is_night; last_lamp_sampling = scene.lamp_sampling; last_shadow_samples = scene.shadow_samples; last_ao = scene.ao; last_render_mode = scene.render_mode; last_sampler = scene.sampler; last_spp = (scene.max_spp, scene.noise_threshold); last_settings = settings; last_lod = scene.lod; last_markers = show_markers;
+        }
+        // la imagen más nueva (terminada o a medias); si es de otra resolución se espera
+        if let Some(img) = worker.latest()
+            && img.dimensions() == (tex_w as u32, tex_h as u32) {
             let _ = rtex.update_texture(img.as_raw());
             rtex_has_image = true;
-            last_eye = eye; last_target = tgt; last_light = scene.light_pos; last_wh = (tex_w, tex_h); last_mode = scene.water_mode; last_is_night = scene.is_night;
-            frame_skip_counter = 0;  // reset counter después de renderizar
         }
 
         // Optimizar scaling - usar nearest neighbor para mejor rendimiento
@@ -217,6 +474,7 @@ fn main() {
         hud.line_col_size(format!("{} FPS", fps), Color::RED, 24);
         let res_label = if half_res { "Low (F1)" } else { "Med (F1)" };  // actualizar labels
         hud.line(format!("RT Res: {}x{}  {}", tex_w, tex_h, res_label));
+        hud.line(format!("Samples: {}/{}  |  Shadow rays: {} (F8)", worker.samples(), Progressive::target_samples(&scene), scene.shadow_samples));
         let day_night_str = if scene.is_night { "Night Mode (F5)" } else { "Day Mode (F5)" };
         hud.line(day_night_str);
         let mode_str = match scene.water_mode {
@@ -225,10 +483,47 @@ fn main() {
             WaterMode::ReflectOnce => "Water: ReflectOnce (slower)",
         };
         hud.line(mode_str);
+        let lamp_str = match scene.lamp_sampling {
+            LampSampling::All => "Lamps: all in range (F7)".to_string(),
+            LampSampling::Stochastic(n) => format!("Lamps: stochastic x{} (F7)", n),
+        };
+        hud.line(lamp_str);
+        let ao_str = match scene.ao {
+            AoMode::Off => "AO: OFF (F9)".to_string(),
+            AoMode::Voxel => "AO: voxel corners (F9)".to_string(),
+            AoMode::RayTraced { radius, samples } => format!("AO: ray traced r={} x{} (F9)", radius, samples),
+        };
+        hud.line(ao_str);
+        if scene.noise_threshold > 0.0 {
+            hud.line(format!("Adaptive: {} threshold, {:.0}% px active (V)  |  Max spp: -/=", scene.noise_threshold, worker.active_fraction() * 100.0));
+        } else {
+            hud.line("Adaptive: OFF (V)  |  Max spp: -/=");
+        }
+        hud.line(format!("Render: {} (F10)  |  Sampler: {} (F11)", scene.render_mode.label(), scene.sampler.label()));
+        hud.line(if settings.denoise { "Denoiser: ON (F2)" } else { "Denoiser: OFF (F2)" });
+        hud.line(if settings.temporal { "Reprojection: ON (F3)" } else { "Reprojection: OFF (F3)" });
+        hud.line(if scene.lod { "Distant LOD: ON (G)" } else { "Distant LOD: OFF (G)" });
+        let (rw, rh) = worker.render_size();
+        match settings.frame_budget {
+            Some(b) => hud.line(format!("Dynamic res: ON, {} ms target, now {}x{} (F4)", b.as_millis(), rw, rh)),
+            None => hud.line("Dynamic res: OFF (F4)"),
+        }
+        if let Some(l) = scene.lights.get(light_rig.selected) {
+            hud.line(format!("Light {}/{}: {}  r={:.2}  (N next | B type | M add | [ ] radius)", light_rig.selected + 1, scene.lights.len(), l.label(), l.radius));
+        }
+        hud.line(if show_markers { "Light markers: ON (Y)" } else { "Light markers: OFF (Y)" });
         hud.line("F5: día/noche  |  F6: toggle water reflections");
         hud.line("Mouse L drag: orbit  |  Wheel: zoom  |  R: reset cámara");
         hud.line("J/L yaw luz  |  I/K pitch  |  U/O radio  |  P spin  |  T reset luz");
-        hud.line("Z/X bajar/subir isla  |  C reset altura  |  H mostrar/ocultar HUD");
+        let xf = &scene.xform;
+        hud.line(format!("World: y {:+.2}  yaw {:.0}°  scale {:.2}  (Z/X | Q/E | ,/. | C reset)", xf.offset.y, xf.yaw.to_degrees(), xf.scale));
+        match stream_stats {
+            Some(st) => {
+                hud.line(st.label());
+                hud.line("Arrows: move  |  H mostrar/ocultar HUD");
+            }
+            None => hud.line("H mostrar/ocultar HUD"),
+        }
         hud.draw(&mut d);
     }
 }
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use raylib::core::math::Vector3;
+
+/// Material de un `.mtl`: color difuso y textura (ruta ya resuelta)
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub kd: Vector3,                 // color difuso (sRGB 0..1)
+    pub map_kd: Option<PathBuf>,     // textura difusa
+}
+
+/// Un triángulo: índices a posiciones, uv y normales (las dos últimas pueden faltar)
+#[derive(Clone, Copy, Debug)]
+pub struct ObjTri {
+    pub v: [usize; 3],
+    pub vt: [Option<usize>; 3],
+    pub vn: [Option<usize>; 3],
+    pub mat: usize, // en `ObjData::materials`
+}
+
+/// Lo que se usa de un `.obj`: vértices, caras en triángulos y materiales
+#[derive(Clone, Debug, Default)]
+pub struct ObjData {
+    pub positions: Vec<Vector3>,
+    pub uvs: Vec<[f32; 2]>,
+    pub normals: Vec<Vector3>,
+    pub tris: Vec<ObjTri>,
+    pub materials: Vec<ObjMaterial>, // siempre hay al menos uno (el de por defecto)
+}
+
+/// Lee un Wavefront `.obj` con sus `mtllib`. Las caras de más de tres vértices se
+/// parten en abanico; de los materiales solo se usan `Kd` y `map_Kd`.
+/// Las líneas que no se entienden se saltan con un aviso.
+pub fn load_obj(path: &Path) -> Result<ObjData, String> {
+    let txt = fs::read_to_string(path).map_err(|e| format!("no se pudo leer {}: {e}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut o = ObjData::default();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut library: HashMap<String, ObjMaterial> = HashMap::new();
+    let mut mat = None;
+
+    for (ln, line) in txt.lines().enumerate() {
+        let mut tok = line.split_whitespace();
+        let Some(key) = tok.next() else { continue; };
+        let rest: Vec<&str> = tok.collect();
+        let ok = match key {
+            "v" => parse_f32s(&rest, 3).map(|f| o.positions.push(Vector3::new(f[0], f[1], f[2]))),
+            "vt" => parse_f32s(&rest, 2).map(|f| o.uvs.push([f[0], f[1]])),
+            "vn" => parse_f32s(&rest, 3).map(|f| o.normals.push(Vector3::new(f[0], f[1], f[2]))),
+            "f" => {
+                let m = *mat.get_or_insert_with(|| material_id(&mut o, &mut by_name, &library, ""));
+                parse_face(&rest, &o, m).map(|tris| o.tris.extend(tris))
+            }
+            "usemtl" => {
+                mat = Some(material_id(&mut o, &mut by_name, &library, rest.first().copied().unwrap_or("")));
+                Some(())
+            }
+            "mtllib" => {
+                for lib in &rest {
+                    match load_mtl(&dir.join(lib)) {
+                        Ok(ms) => library.extend(ms.into_iter().map(|m| (m.name.clone(), m))),
+                        Err(e) => eprintln!("WARN: {e}"),
+                    }
+                }
+                Some(())
+            }
+            _ => Some(()), // o, g, s, comentarios...
+        };
+        if ok.is_none() {
+            eprintln!("WARN: {}:{} línea de obj inválida: {line}", path.display(), ln + 1);
+        }
+    }
+    if o.materials.is_empty() {
+        material_id(&mut o, &mut by_name, &library, "");
+    }
+    Ok(o)
+}
+
+/// Índice del material `name` en `o.materials` (se agrega la primera vez; si la
+/// biblioteca no lo tiene queda gris claro)
+fn material_id(o: &mut ObjData, by_name: &mut HashMap<String, usize>, library: &HashMap<String, ObjMaterial>, name: &str) -> usize {
+    *by_name.entry(name.to_string()).or_insert_with(|| {
+        let m = library.get(name).cloned().unwrap_or_else(|| {
+            if !name.is_empty() { eprintln!("WARN: material {name} no está en ningún mtllib"); }
+            ObjMaterial { name: name.to_string(), kd: Vector3::new(0.8, 0.8, 0.8), map_kd: None }
+        });
+        o.materials.push(m);
+        o.materials.len() - 1
+    })
+}
+
+/// Materiales de un `.mtl` (las rutas de las texturas quedan relativas a su carpeta)
+fn load_mtl(path: &Path) -> Result<Vec<ObjMaterial>, String> {
+    let txt = fs::read_to_string(path).map_err(|e| format!("no se pudo leer {}: {e}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut out: Vec<ObjMaterial> = Vec::new();
+    for line in txt.lines() {
+        let mut tok = line.split_whitespace();
+        match (tok.next(), out.last_mut()) {
+            (Some("newmtl"), _) => out.push(ObjMaterial {
+                name: tok.next().unwrap_or("").to_string(), kd: Vector3::new(0.8, 0.8, 0.8), map_kd: None,
+            }),
+            (Some("Kd"), Some(m)) => {
+                if let Some(f) = parse_f32s(&tok.collect::<Vec<_>>(), 3) { m.kd = Vector3::new(f[0], f[1], f[2]); }
+            }
+            // la ruta es lo último de la línea (puede haber opciones antes)
+            (Some("map_Kd"), Some(m)) => m.map_kd = tok.last().map(|f| dir.join(f)),
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Los números de la línea, si son al menos `need`
+fn parse_f32s(tok: &[&str], need: usize) -> Option<Vec<f32>> {
+    let f: Vec<f32> = tok.iter().map(|t| t.parse().ok()).collect::<Option<_>>()?;
+    (f.len() >= need).then_some(f)
+}
+
+/// Una cara `f a/b/c ...` en triángulos (índices desde 1 o negativos desde el final)
+fn parse_face(tok: &[&str], o: &ObjData, mat: usize) -> Option<Vec<ObjTri>> {
+    if tok.len() < 3 { return None; }
+    let index = |s: &str, len: usize| -> Option<Option<usize>> {
+        if s.is_empty() { return Some(None); }
+        let i: i64 = s.parse().ok()?;
+        let i = if i < 0 { len as i64 + i } else { i - 1 };
+        (0..len as i64).contains(&i).then_some(Some(i as usize))
+    };
+    let corners: Vec<(usize, Option<usize>, Option<usize>)> = tok.iter().map(|c| {
+        let mut parts = c.split('/');
+        let v = index(parts.next()?, o.positions.len())??;
+        let vt = index(parts.next().unwrap_or(""), o.uvs.len())?;
+        let vn = index(parts.next().unwrap_or(""), o.normals.len())?;
+        Some((v, vt, vn))
+    }).collect::<Option<_>>()?;
+    Some((1..corners.len() - 1).map(|k| {
+        let c = [corners[0], corners[k], corners[k + 1]];
+        ObjTri { v: c.map(|c| c.0), vt: c.map(|c| c.1), vn: c.map(|c| c.2), mat }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cuatro vértices, dos uv y una normal
+    fn data() -> ObjData {
+        ObjData {
+            positions: vec![Vector3::zero(); 4],
+            uvs: vec![[0.0, 0.0]; 2],
+            normals: vec![Vector3::new(0.0, 1.0, 0.0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn face_with_positive_indices() {
+        let tris = parse_face(&["1/1/1", "2/2/1", "3/1/1"], &data(), 0).unwrap();
+        assert_eq!(tris.len(), 1);
+        assert_eq!(tris[0].v, [0, 1, 2]);
+        assert_eq!(tris[0].vt, [Some(0), Some(1), Some(0)]);
+        assert_eq!(tris[0].vn, [Some(0); 3]);
+    }
+
+    #[test]
+    fn face_with_negative_indices_counts_from_the_end() {
+        let tris = parse_face(&["-4", "-3", "-1"], &data(), 2).unwrap();
+        assert_eq!(tris[0].v, [0, 1, 3]);
+        assert_eq!(tris[0].vt, [None; 3]);
+        assert_eq!(tris[0].mat, 2);
+        let tris = parse_face(&["-1/-2/-1", "1/-1/1", "2//-1"], &data(), 0).unwrap();
+        assert_eq!(tris[0].v, [3, 0, 1]);
+        assert_eq!(tris[0].vt, [Some(0), Some(1), None]);
+        assert_eq!(tris[0].vn, [Some(0); 3]);
+    }
+
+    #[test]
+    fn polygon_becomes_a_fan() {
+        let tris = parse_face(&["1", "2", "3", "4"], &data(), 0).unwrap();
+        assert_eq!(tris.iter().map(|t| t.v).collect::<Vec<_>>(), [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn bad_faces_are_rejected() {
+        let o = data();
+        assert!(parse_face(&["1", "2"], &o, 0).is_none());        // menos de tres vértices
+        assert!(parse_face(&["1", "2", "5"], &o, 0).is_none());   // más allá del final
+        assert!(parse_face(&["0", "1", "2"], &o, 0).is_none());   // los índices empiezan en 1
+        assert!(parse_face(&["-5", "1", "2"], &o, 0).is_none());  // antes del principio
+        assert!(parse_face(&["1/3", "2", "3"], &o, 0).is_none()); // uv que no existe
+        assert!(parse_face(&["x", "2", "3"], &o, 0).is_none());
+    }
+}
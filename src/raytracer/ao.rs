@@ -0,0 +1,89 @@
+use raylib::core::math::Vector3;
+
+use crate::geometry::{Hit, Surface};
+use crate::light::basis;
+use crate::world::BlockKind;
+use crate::raytracer::{AoMode, SceneRT};
+
+use super::frame::Frame;
+use super::sampler::Sampler;
+
+/// Oclusión ambiental en el punto (1 = nada tapa, 0 = todo tapado)
+pub fn ambient_occlusion(frame: &Frame, scene: &SceneRT, hit: &Hit, smp: &mut dyn Sampler) -> f32 {
+    occlusion(frame, scene, hit, scene.ao, smp)
+}
+
+/// Igual, pero con un modo de AO dado (la vista de solo AO lo fuerza)
+pub fn occlusion(frame: &Frame, scene: &SceneRT, hit: &Hit, mode: AoMode, smp: &mut dyn Sampler) -> f32 {
+    match mode {
+        AoMode::Off => 1.0,
+        AoMode::Voxel => voxel_ao(frame, hit),
+        AoMode::RayTraced { radius, samples } => ray_ao(frame, scene, hit, radius, samples, smp),
+    }
+}
+
+// Brillo según cuántos vecinos tapan la esquina (0 = esquina metida, 3 = libre)
+const AO_LEVELS: [f32; 4] = [0.35, 0.55, 0.78, 1.0];
+
+/// AO estilo Minecraft: cada esquina de la cara mira los 3 vecinos de la capa
+/// de aire frente a la cara (2 lados + diagonal) y se interpola bilineal.
+fn voxel_ao(frame: &Frame, hit: &Hit) -> f32 {
+    if hit.surface != Surface::Block { return 1.0; } // las mallas no tienen celdas vecinas
+    // todo en las celdas de la rejilla que se golpeó (el mundo o el modelo de una copia)
+    let (g, xf) = frame.space(hit.inst);
+    let (p, n) = (xf.point_to_local(hit.p), xf.normal_to_local(hit.n));
+    // ejes: normal y dos tangentes de la cara (enteros)
+    let axis = if n.x.abs() > 0.5 { 0 } else if n.y.abs() > 0.5 { 1 } else { 2 };
+    let (ta, tb) = match axis { 0 => (1, 2), 1 => (0, 2), _ => (0, 1) };
+    let comp = |v: Vector3, i: usize| [v.x, v.y, v.z][i];
+
+    // celda de aire justo frente a la cara
+    let (cx, cy, cz) = g.cell_of(p + n * 0.5);
+    let air = [cx, cy, cz];
+    let solid = |da: i32, db: i32| {
+        let mut c = air;
+        c[ta] += da;
+        c[tb] += db;
+        matches!(g.get(c[0], c[1], c[2]), Some(k) if k != BlockKind::Water && k.is_cube())
+    };
+    let corner = |sa: i32, sb: i32| {
+        let (s1, s2, c) = (solid(sa, 0), solid(0, sb), solid(sa, sb));
+        let level = if s1 && s2 { 0 } else { 3 - (s1 as usize + s2 as usize + c as usize) };
+        AO_LEVELS[level]
+    };
+
+    // posición dentro de la cara (0..1 en cada tangente)
+    let local = p - g.min;
+    let fa = comp(local, ta) - comp(local, ta).floor();
+    let fb = comp(local, tb) - comp(local, tb).floor();
+
+    let (c00, c10, c01, c11) = (corner(-1, -1), corner(1, -1), corner(-1, 1), corner(1, 1));
+    let lo = c00 * (1.0 - fa) + c10 * fa;
+    let hi = c01 * (1.0 - fa) + c11 * fa;
+    lo * (1.0 - fb) + hi * fb
+}
+
+/// AO trazado: rayos con distribución coseno sobre el hemisferio, de largo `radius`.
+/// El ruido cambia con cada muestra progresiva, así converge acumulando.
+fn ray_ao(frame: &Frame, scene: &SceneRT, hit: &Hit, radius: f32, samples: u32, smp: &mut dyn Sampler) -> f32 {
+    let n = hit.n;
+    let (t, b) = basis(n);
+    let o = hit.p + n * 1e-3;
+    let samples = samples.max(1);
+
+    let mut open = 0u32;
+    for _ in 0..samples {
+        let [u1, u2] = smp.next_2d();
+        // coseno: disco uniforme proyectado al hemisferio
+        let r = u1.sqrt();
+        let phi = std::f32::consts::TAU * u2;
+        let (x, y) = (r * phi.cos(), r * phi.sin());
+        let z = (1.0 - u1).max(0.0).sqrt();
+        let d = (t * x + b * y + n * z).normalized();
+        match frame.trace(scene, o, d) {
+            Some(h) if h.t < radius && h.kind != BlockKind::Water => {}
+            _ => open += 1,
+        }
+    }
+    open as f32 / samples as f32
+}
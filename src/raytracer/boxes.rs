@@ -0,0 +1,92 @@
+use raylib::core::math::Vector3;
+
+use crate::world::BlockKind;
+
+use super::grid::{ray_aabb, Grid};
+
+/// Medio ancho del poste de una cerca y de sus travesaños (en bloques)
+const FENCE_POST: f32 = 0.125;
+const FENCE_RAIL: f32 = 0.0625;
+/// Alturas de los dos travesaños de una cerca
+const FENCE_RAILS: [(f32, f32); 2] = [(0.375, 0.5625), (0.75, 0.9375)];
+/// Medio grosor de una ventana
+const PANE_HALF: f32 = 0.0625;
+
+/// Qué vecinos (-X, +X, -Z, +Z) tiene un bloque con forma: para las cercas y ventanas,
+/// otra igual o un bloque entero (no agua ni hojas) donde unirse; para las escaleras,
+/// solo el bloque entero hacia el que suben
+fn connections(g: &Grid, [x, y, z]: [i32; 3], kind: BlockKind) -> [bool; 4] {
+    let conn = [(-1, 0), (1, 0), (0, -1), (0, 1)].map(|(dx, dz)| match g.get(x + dx, y, z + dz) {
+        Some(k) => (k == kind && kind != BlockKind::Stairs) || (k.is_cube() && !matches!(k, BlockKind::Water | BlockKind::Leaves)),
+        None => false,
+    });
+    if kind != BlockKind::Stairs { return conn; }
+    // el primero que haya; sin ninguno suben hacia -Z
+    let up = conn.iter().position(|&c| c).unwrap_or(2);
+    std::array::from_fn(|i| i == up)
+}
+
+/// Llama a `f` con cada caja (esquina mínima y máxima, 0..1 dentro de la celda) del
+/// bloque `kind`; `conn` dice hacia qué vecinos se estira (ver `connections`)
+fn for_each_box(kind: BlockKind, conn: [bool; 4], mut f: impl FnMut(Vector3, Vector3)) {
+    let b = |x0, y0, z0, x1, y1, z1| (Vector3::new(x0, y0, z0), Vector3::new(x1, y1, z1));
+    // brazo de media celda desde el centro hacia el vecino `i`, de medio ancho `r`
+    let arm = |i: usize, r: f32, y0: f32, y1: f32| match i {
+        0 => b(0.0, y0, 0.5 - r, 0.5, y1, 0.5 + r),
+        1 => b(0.5, y0, 0.5 - r, 1.0, y1, 0.5 + r),
+        2 => b(0.5 - r, y0, 0.0, 0.5 + r, y1, 0.5),
+        _ => b(0.5 - r, y0, 0.5, 0.5 + r, y1, 1.0),
+    };
+    let mut emit = |(mn, mx): (Vector3, Vector3)| f(mn, mx);
+    match kind {
+        BlockKind::Slab => emit(b(0.0, 0.0, 0.0, 1.0, 0.5, 1.0)),
+        BlockKind::Stairs => {
+            emit(b(0.0, 0.0, 0.0, 1.0, 0.5, 1.0));
+            // el escalón de arriba, en la mitad hacia donde sube
+            emit(match conn.iter().position(|&c| c) {
+                Some(0) => b(0.0, 0.5, 0.0, 0.5, 1.0, 1.0),
+                Some(1) => b(0.5, 0.5, 0.0, 1.0, 1.0, 1.0),
+                Some(3) => b(0.0, 0.5, 0.5, 1.0, 1.0, 1.0),
+                _ => b(0.0, 0.5, 0.0, 1.0, 1.0, 0.5),
+            });
+        }
+        BlockKind::Fence => {
+            emit(b(0.5 - FENCE_POST, 0.0, 0.5 - FENCE_POST, 0.5 + FENCE_POST, 1.0, 0.5 + FENCE_POST));
+            for i in (0..4).filter(|&i| conn[i]) {
+                for (y0, y1) in FENCE_RAILS { emit(arm(i, FENCE_RAIL, y0, y1)); }
+            }
+        }
+        BlockKind::Pane => {
+            // sola queda de lado a lado en X
+            let conn = if conn.iter().any(|&c| c) { conn } else { [true, true, false, false] };
+            emit(b(0.5 - PANE_HALF, 0.0, 0.5 - PANE_HALF, 0.5 + PANE_HALF, 1.0, 0.5 + PANE_HALF));
+            for i in (0..4).filter(|&i| conn[i]) { emit(arm(i, PANE_HALF, 0.0, 1.0)); }
+        }
+        _ => emit(b(0.0, 0.0, 0.0, 1.0, 1.0, 1.0)),
+    }
+}
+
+/// Primer impacto con las cajas del bloque `kind` (que no es un cubo) en la celda
+/// `cell` de la rejilla fina: `t` y normal de la cara por donde entra el rayo
+pub fn shaped_hit(o: Vector3, d: Vector3, g: &Grid, cell: [i32; 3], kind: BlockKind) -> Option<(f32, Vector3)> {
+    let conn = if kind == BlockKind::Slab { [false; 4] } else { connections(g, cell, kind) };
+    let base = g.min + Vector3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32);
+    let mut best: Option<(f32, Vector3)> = None;
+    for_each_box(kind, conn, |mn, mx| {
+        let (mn, mx) = (base + mn, base + mx);
+        let Some((t, _)) = ray_aabb(o, d, mn, mx) else { return; };
+        if best.is_some_and(|(b, _)| b <= t) { return; }
+        // la cara de entrada es la del eje que el rayo cruza último
+        let near = |a: f32, b: f32, o: f32, d: f32| if d != 0.0 { ((a - o) / d).min((b - o) / d) } else { f32::NEG_INFINITY };
+        let tn = [near(mn.x, mx.x, o.x, d.x), near(mn.y, mx.y, o.y, d.y), near(mn.z, mx.z, o.z, d.z)];
+        let n = if tn[0] >= tn[1] && tn[0] >= tn[2] {
+            Vector3::new(-d.x.signum(), 0.0, 0.0)
+        } else if tn[1] >= tn[2] {
+            Vector3::new(0.0, -d.y.signum(), 0.0)
+        } else {
+            Vector3::new(0.0, 0.0, -d.z.signum())
+        };
+        best = Some((t, n));
+    });
+    best
+}
@@ -1,7 +1,7 @@
 use raylib::core::math::Vector3;
 use crate::camera::OrbitCamRT;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct CamPre {
     pub eye: Vector3,
     pub fwd: Vector3,
@@ -22,8 +22,14 @@ pub fn precompute(cam: &OrbitCamRT) -> CamPre {
 
 #[inline]
 pub fn primary_dir(pre: &CamPre, x: u32, y: u32, w: u32, h: u32) -> Vector3 {
-    let ndc_x = (x as f32 + 0.5) / w as f32;
-    let ndc_y = (y as f32 + 0.5) / h as f32;
+    primary_dir_at(pre, x as f32 + 0.5, y as f32 + 0.5, w, h)
+}
+
+/// Igual que `primary_dir` pero en una posición cualquiera dentro del pixel
+#[inline]
+pub fn primary_dir_at(pre: &CamPre, fx: f32, fy: f32, w: u32, h: u32) -> Vector3 {
+    let ndc_x = fx / w as f32;
+    let ndc_y = fy / h as f32;
     let px = (2.0 * ndc_x - 1.0) * pre.aspect * pre.tan_half;
     let py = (1.0 - 2.0 * ndc_y) * pre.tan_half;
     (pre.fwd + pre.right.scale_by(px) + pre.up.scale_by(py)).normalized()
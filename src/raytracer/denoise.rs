@@ -0,0 +1,185 @@
+use std::thread;
+use raylib::core::math::Vector3;
+
+/// Profundidad que se le pone al cielo (lejos de cualquier bloque)
+pub const SKY_DEPTH: f32 = 1.0e3;
+
+/// Datos del primer impacto de un pixel, salen junto con la imagen en `render_mt`
+/// y guían al denoiser. Se promedian entre muestras como el color.
+#[derive(Clone, Copy, Debug)]
+pub struct AuxPixel {
+    pub normal: Vector3, // cero en el cielo
+    pub depth: f32,
+    pub albedo: Vector3, // lineal; 1 en el cielo
+}
+
+impl AuxPixel {
+    pub const SKY: AuxPixel = AuxPixel { normal: Vector3::new(0.0, 0.0, 0.0), depth: SKY_DEPTH, albedo: Vector3::new(1.0, 1.0, 1.0) };
+    pub const ZERO: AuxPixel = AuxPixel { normal: Vector3::new(0.0, 0.0, 0.0), depth: 0.0, albedo: Vector3::new(0.0, 0.0, 0.0) };
+}
+
+// Pasadas del à-trous (saltos 1, 2, 4, 8, 16) y fuerza de cada guía (valores de SVGF)
+const ITERATIONS: u32 = 5;
+const SIGMA_L: f32 = 4.0;
+const SIGMA_Z: f32 = 1.0;
+const PHI_N: i32 = 128;
+// Con menos muestras que esto la varianza por pixel no sirve y se estima en la vecindad
+const MIN_TEMPORAL: u32 = 4;
+// B3-spline 1D: el kernel 5x5 es el producto
+const KERNEL: [f32; 3] = [3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+const EPS_ALBEDO: f32 = 1e-3;
+
+#[inline] pub fn luminance(c: Vector3) -> f32 { 0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z }
+
+#[inline]
+pub fn demodulate(c: Vector3, a: Vector3) -> Vector3 {
+    Vector3::new(c.x / a.x.max(EPS_ALBEDO), c.y / a.y.max(EPS_ALBEDO), c.z / a.z.max(EPS_ALBEDO))
+}
+
+/// Filtro à-trous guiado (estilo SVGF) sobre la imagen acumulada.
+///
+/// `color` y `aux` son promedios lineales de `counts[i]` muestras (con muestreo adaptativo
+/// cada pixel lleva las suyas) y `moments` las sumas de la luminancia de la irradiancia
+/// y de su cuadrado. Se filtra la irradiancia (color / albedo) para no borrar las
+/// texturas, con pesos que paran en cambios de normal, de profundidad y de luminancia
+/// (según la varianza que queda), y al final se vuelve a multiplicar por el albedo.
+pub fn denoise(w: u32, h: u32, color: &[Vector3], aux: &[AuxPixel], moments: &[[f32; 2]], counts: &[u32]) -> Vec<Vector3> {
+    let (wu, hu) = (w as usize, h as usize);
+    let normals: Vec<Vector3> = aux.iter()
+        .map(|a| if a.normal.length() > 1e-4 { a.normal.normalized() } else { Vector3::zero() })
+        .collect();
+    let depth: Vec<f32> = aux.iter().map(|a| a.depth).collect();
+    let mut irr: Vec<Vector3> = color.iter().zip(aux).map(|(&c, a)| demodulate(c, a.albedo)).collect();
+
+    // gradiente de profundidad en pantalla (para comparar profundidades a escala)
+    let dz: Vec<f32> = (0..wu * hu).map(|i| {
+        let (x, y) = (i % wu, i / wu);
+        let at = |xx: usize, yy: usize| depth[yy * wu + xx];
+        let gx = (at((x + 1).min(wu - 1), y) - at(x.saturating_sub(1), y)).abs() * 0.5;
+        let gy = (at(x, (y + 1).min(hu - 1)) - at(x, y.saturating_sub(1))).abs() * 0.5;
+        gx.max(gy)
+    }).collect();
+
+    // varianza del promedio: temporal si el pixel ya tiene muestras, si no en una vecindad 5x5
+    let mut var: Vec<f32> = (0..wu * hu).map(|p| {
+        let n = counts[p].max(1);
+        if n >= MIN_TEMPORAL {
+            let (m1, m2) = (moments[p][0] / n as f32, moments[p][1] / n as f32);
+            (m2 - m1 * m1).max(0.0) / n as f32
+        } else {
+            spatial_variance(wu, hu, p, &irr, &normals, &depth, n)
+        }
+    }).collect();
+
+    let g = Guides { w: wu, h: hu, normals: &normals, depth: &depth, dz: &dz };
+    for it in 0..ITERATIONS {
+        let (ni, nv) = atrous_pass(&g, &irr, &var, 1 << it);
+        irr = ni;
+        var = nv;
+    }
+
+    irr.iter().zip(aux).map(|(&e, a)| e * a.albedo).collect()
+}
+
+struct Guides<'a> {
+    w: usize, h: usize,
+    normals: &'a [Vector3],
+    depth: &'a [f32],
+    dz: &'a [f32],
+}
+
+/// Varianza de la luminancia del pixel `p` entre vecinos parecidos (misma normal y profundidad cercana)
+fn spatial_variance(w: usize, h: usize, p: usize, irr: &[Vector3], normals: &[Vector3], depth: &[f32], n: u32) -> f32 {
+    let (px, py) = ((p % w) as i32, (p / w) as i32);
+    let (mut s1, mut s2, mut sw) = (0.0, 0.0, 0.0);
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            let (qx, qy) = (px + dx, py + dy);
+            if qx < 0 || qy < 0 || qx >= w as i32 || qy >= h as i32 { continue; }
+            let q = qy as usize * w + qx as usize;
+            if normals[p].dot(normals[q]) < 0.9 || (depth[p] - depth[q]).abs() > 0.1 * depth[p] { continue; }
+            let l = luminance(irr[q]);
+            s1 += l;
+            s2 += l * l;
+            sw += 1.0;
+        }
+    }
+    if sw < 2.0 { return 0.0; }
+    let m = s1 / sw;
+    (s2 / sw - m * m).max(0.0) / n as f32
+}
+
+/// Una pasada del à-trous con salto `step`, repartida por filas entre hilos
+fn atrous_pass(g: &Guides, irr: &[Vector3], var: &[f32], step: i32) -> (Vec<Vector3>, Vec<f32>) {
+    let var_blur = blur3(g.w, g.h, var);
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(g.h).max(1);
+    let rows_per = g.h.div_ceil(threads);
+
+    let mut out_irr = vec![Vector3::zero(); g.w * g.h];
+    let mut out_var = vec![0.0f32; g.w * g.h];
+    thread::scope(|s| {
+        for (t, (ci, cv)) in out_irr.chunks_mut(rows_per * g.w).zip(out_var.chunks_mut(rows_per * g.w)).enumerate() {
+            let var_blur = &var_blur;
+            s.spawn(move || {
+                let y0 = t * rows_per;
+                for (i, (oi, ov)) in ci.iter_mut().zip(cv.iter_mut()).enumerate() {
+                    let p = y0 * g.w + i;
+                    (*oi, *ov) = filter_pixel(g, irr, var, var_blur, p, step);
+                }
+            });
+        }
+    });
+    (out_irr, out_var)
+}
+
+#[inline]
+fn filter_pixel(g: &Guides, irr: &[Vector3], var: &[f32], var_blur: &[f32], p: usize, step: i32) -> (Vector3, f32) {
+    let (px, py) = ((p % g.w) as i32, (p / g.w) as i32);
+    let np = g.normals[p];
+    // cielo o sin ruido: nada que filtrar
+    if np.dot(np) == 0.0 || var_blur[p] <= 0.0 { return (irr[p], var[p]); }
+    let lp = luminance(irr[p]);
+    let sigma_l = SIGMA_L * var_blur[p].sqrt() + 1e-6;
+    let sigma_z = SIGMA_Z * g.dz[p].max(1e-3);
+
+    let (mut sum, mut sum_v, mut sum_w) = (Vector3::zero(), 0.0, 0.0);
+    for dy in -2i32..=2 {
+        for dx in -2i32..=2 {
+            let (qx, qy) = (px + dx * step, py + dy * step);
+            if qx < 0 || qy < 0 || qx >= g.w as i32 || qy >= g.h as i32 { continue; }
+            let q = qy as usize * g.w + qx as usize;
+            let k = KERNEL[dx.unsigned_abs() as usize] * KERNEL[dy.unsigned_abs() as usize];
+
+            let w_n = np.dot(g.normals[q]).max(0.0).powi(PHI_N);
+            let dist = ((dx * dx + dy * dy) as f32).sqrt() * step as f32;
+            let w_z = (g.depth[p] - g.depth[q]).abs() / (sigma_z * dist + 1e-3);
+            let w_l = (lp - luminance(irr[q])).abs() / sigma_l;
+            let wt = k * w_n * (-w_z - w_l).exp();
+
+            sum += irr[q] * wt;
+            sum_v += wt * wt * var[q];
+            sum_w += wt;
+        }
+    }
+    if sum_w <= 0.0 { return (irr[p], var[p]); }
+    (sum / sum_w, sum_v / (sum_w * sum_w))
+}
+
+/// Gauss 3x3 de la varianza (la luminancia se compara contra esta, menos ruidosa)
+fn blur3(w: usize, h: usize, v: &[f32]) -> Vec<f32> {
+    const K: [f32; 2] = [0.5, 0.25];
+    (0..w * h).map(|p| {
+        let (px, py) = ((p % w) as i32, (p / w) as i32);
+        let (mut s, mut sw) = (0.0, 0.0);
+        for dy in -1i32..=1 {
+            for dx in -1i32..=1 {
+                let (qx, qy) = (px + dx, py + dy);
+                if qx < 0 || qy < 0 || qx >= w as i32 || qy >= h as i32 { continue; }
+                let k = K[dx.unsigned_abs() as usize] * K[dy.unsigned_abs() as usize];
+                s += v[qy as usize * w + qx as usize] * k;
+                sw += k;
+            }
+        }
+        s / sw
+    }).collect()
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use raylib::core::math::Vector3;
+
+use crate::geometry::WorldXform;
+
+use super::SceneRT;
+use super::cam::{precompute, CamPre};
+use super::grid::{trace_grid_first, trace_grid_lod, DdaHit, Grid, MIP_LEVELS};
+use super::instances::Instances;
+use super::lamps::LampIndex;
+use super::mesh::Meshes;
+use super::shapes::Shapes;
+
+/// Un nivel de detalle empieza donde sus celdas ya no miden más que esto en pixeles
+/// (más = se pasa antes a lo grueso: más rápido y más borroso)
+const LOD_CELL_PIXELS: f32 = 1.0;
+
+/// Distancia a la que los rayos primarios de una imagen de `h` filas pasan a cada
+/// nivel de detalle. El cono de un pixel tiene de ancho t · (2·tan(fov/2) / h) a
+/// distancia t; el nivel n (celdas de 2ⁿ) se usa desde que una celda suya cabe en
+/// LOD_CELL_PIXELS pixeles.
+pub fn lod_distances(pre: &CamPre, h: u32) -> [f32; MIP_LEVELS] {
+    let spread = 2.0 * pre.tan_half / h.max(1) as f32;
+    std::array::from_fn(|i| (2 << i) as f32 / (spread * LOD_CELL_PIXELS))
+}
+
+/// Sin niveles de detalle: todo en la rejilla fina
+pub const NO_LOD: [f32; MIP_LEVELS] = [f32::INFINITY; MIP_LEVELS];
+
+/// Distancias de `lod_distances` para la escena (más lejos si los bloques son más grandes)
+pub fn scene_lod(scene: &SceneRT, pre: &CamPre, h: u32) -> [f32; MIP_LEVELS] {
+    if scene.lod { lod_distances(pre, h).map(|t| t * scene.xform.scale) } else { NO_LOD }
+}
+
+/// Todo lo que se deriva de la escena una vez por frame (se comparte entre hilos)
+pub struct Frame {
+    pub pre: CamPre,
+    pub grid: Arc<Grid>,
+    pub xform: WorldXform, // los rayos se trazan en los bloques y los impactos vuelven a la escena
+    pub instances: Arc<Instances>,
+    pub meshes: Arc<Meshes>,
+    pub shapes: Arc<Shapes>,
+    pub lamps: LampIndex,
+    pub sample: u32,    // índice de la muestra progresiva (0 = primera)
+    pub skip_empty: bool, // saltar el espacio vacío en el DDA (false = celda por celda)
+    pub lod: [f32; MIP_LEVELS], // distancia a la que los rayos primarios pasan a cada nivel de detalle
+}
+
+impl Frame {
+    pub fn new(scene: &SceneRT, sample: u32) -> Self {
+        Self {
+            pre: precompute(&scene.cam),
+            grid: scene.grid.clone(),
+            xform: scene.xform,
+            instances: scene.instances.clone(),
+            meshes: scene.meshes.clone(),
+            shapes: scene.shapes.clone(),
+            lamps: LampIndex::build(
+                std::iter::once((&scene.blocks[..], scene.xform)).chain(scene.instances.placed(&scene.xform)),
+                scene.time,
+            ),
+            sample,
+            skip_empty: true,
+            lod: NO_LOD,
+        }
+    }
+
+    /// Prende los niveles de detalle (si la escena los usa) para una imagen de `h` filas
+    pub fn set_lod(&mut self, scene: &SceneRT, h: u32) {
+        self.lod = scene_lod(scene, &self.pre, h);
+    }
+
+    /// Rejilla de lo que se golpeó (el mundo o el modelo de una copia) y cómo se pone en la escena
+    #[inline]
+    pub fn space(&self, inst: Option<u32>) -> (&Grid, WorldXform) {
+        match inst {
+            None => (&self.grid, self.xform),
+            Some(i) => {
+                let inst = &self.instances.list[i as usize];
+                (&self.instances.models[inst.model].grid, self.xform.compose(&inst.xform))
+            }
+        }
+    }
+
+    /// Centro (en la escena) de la celda donde cae el punto `p` de la escena, en la
+    /// rejilla del mundo o de la copia `inst`
+    #[inline]
+    pub fn cell_center(&self, p: Vector3, inst: Option<u32>) -> Vector3 {
+        let (g, xf) = self.space(inst);
+        let (x, y, z) = g.cell_of(xf.point_to_local(p));
+        xf.point_to_world(g.min + Vector3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5))
+    }
+
+    /// Primer impacto contra el mundo
+    #[inline]
+    pub fn trace(&self, scene: &SceneRT, o: Vector3, d: Vector3) -> Option<DdaHit> {
+        let (ol, dl) = (self.xform.point_to_local(o), self.xform.ray_dir_to_local(d));
+        let hit = trace_grid_first(ol, dl, &self.grid, self.skip_empty, &scene.mats, scene.is_night);
+        self.nearest(scene, ol, dl, hit).map(|h| h.to_world(&self.xform))
+    }
+
+    /// Lo más cercano entre `hit` (la rejilla del mundo) y lo que está fuera de ella:
+    /// las copias de modelos, las mallas y las formas. El rayo va en los bloques.
+    #[inline]
+    fn nearest(&self, scene: &SceneRT, o: Vector3, d: Vector3, hit: Option<DdaHit>) -> Option<DdaHit> {
+        let hit = self.instances.nearest(o, d, hit, self.skip_empty, &scene.mats, scene.is_night);
+        let hit = self.meshes.nearest(o, d, hit);
+        self.shapes.nearest(o, d, hit)
+    }
+
+    /// Primer impacto de un rayo primario, pasando a los niveles de detalle gruesos
+    /// con la distancia (ver `set_lod`)
+    #[inline]
+    pub fn trace_primary(&self, scene: &SceneRT, o: Vector3, d: Vector3) -> Option<DdaHit> {
+        let (ol, dl) = (self.xform.point_to_local(o), self.xform.ray_dir_to_local(d));
+        let hit = trace_grid_lod(ol, dl, &self.grid, &self.lod, self.skip_empty, &scene.mats, scene.is_night);
+        self.nearest(scene, ol, dl, hit).map(|h| h.to_world(&self.xform))
+    }
+}
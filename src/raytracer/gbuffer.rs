@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::geometry::WorldXform;
+
+use super::{RenderOpts, SceneRT};
+use super::cam::{precompute, primary_dir, CamPre};
+use super::frame::{scene_lod, Frame, NO_LOD};
+use super::grid::{DdaHit, Grid, MIP_LEVELS};
+use super::instances::Instances;
+use super::mesh::Meshes;
+use super::renderer::{frame_for, BAND_ROWS};
+use super::shapes::Shapes;
+
+/// Primer impacto del rayo central de cada pixel (punto, normal, uv, cara, tipo).
+/// Mientras no cambien la cámara ni lo que hay en el mundo (bloques, copias, mallas,
+/// formas) sirve para volver a sombrear (mover la luz, día/noche, AO...) sin recorrer
+/// la rejilla otra vez.
+pub struct GBuffer {
+    w: u32, h: u32,
+    pre: Option<CamPre>,
+    grid: Option<Arc<Grid>>,
+    instances: Option<Arc<Instances>>,
+    meshes: Option<Arc<Meshes>>,
+    shapes: Option<Arc<Shapes>>,
+    xform: WorldXform,
+    lod: [f32; MIP_LEVELS],
+    hits: Vec<Option<DdaHit>>,
+}
+
+impl GBuffer {
+    pub fn new() -> Self {
+        Self {
+            w: 0, h: 0, pre: None,
+            grid: None, instances: None, meshes: None, shapes: None,
+            xform: WorldXform::default(), lod: NO_LOD, hits: Vec::new(),
+        }
+    }
+
+    pub fn hits(&self) -> &[Option<DdaHit>] { &self.hits }
+
+    /// Deja el buffer al día con la vista de la escena; solo traza si la cámara, el tamaño,
+    /// los bloques, las copias, las mallas, las formas (p. ej. los marcadores al mover una
+    /// luz), dónde están o los niveles de detalle cambiaron (día/noche no cambia qué se ve).
+    /// `opts` dice cómo trazar (salto de vacío), igual que en el render.
+    pub fn update(&mut self, scene: &SceneRT, w: u32, h: u32, opts: &RenderOpts) {
+        let pre = precompute(&scene.cam);
+        // todo eso se rehace entero cuando cambia: basta comparar los punteros
+        let same_world = same(&self.grid, &scene.grid) && same(&self.instances, &scene.instances)
+            && same(&self.meshes, &scene.meshes) && same(&self.shapes, &scene.shapes);
+        let lod = scene_lod(scene, &pre, h);
+        if (w, h) == (self.w, self.h) && self.pre == Some(pre) && same_world && self.xform == scene.xform && self.lod == lod {
+            return;
+        }
+        self.hits = trace_primary(scene, &frame_for(scene, 0, h, opts), w, h);
+        (self.w, self.h, self.pre, self.xform, self.lod) = (w, h, Some(pre), scene.xform, lod);
+        self.grid = Some(scene.grid.clone());
+        self.instances = Some(scene.instances.clone());
+        self.meshes = Some(scene.meshes.clone());
+        self.shapes = Some(scene.shapes.clone());
+    }
+}
+
+/// ¿Lo guardado es el mismo `Arc` que tiene la escena ahora?
+fn same<T>(cached: &Option<Arc<T>>, now: &Arc<T>) -> bool {
+    cached.as_ref().is_some_and(|c| Arc::ptr_eq(c, now))
+}
+
+/// Traza los rayos centrales de toda la imagen con `frame` (como el render, ver
+/// `Frame::trace_primary`), en franjas que los hilos se van repartiendo
+fn trace_primary(scene: &SceneRT, frame: &Frame, w: u32, h: u32) -> Vec<Option<DdaHit>> {
+    let bands = h.div_ceil(BAND_ROWS);
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(bands as usize).max(1);
+
+    let next = AtomicU32::new(0);
+    let done: Mutex<Vec<Vec<Option<DdaHit>>>> = Mutex::new(vec![Vec::new(); bands as usize]);
+    thread::scope(|s| {
+        for _ in 0..threads {
+            let (next, done) = (&next, &done);
+            s.spawn(move || loop {
+                let b = next.fetch_add(1, Ordering::Relaxed);
+                if b >= bands { break; }
+                let (y0, y1) = (b * BAND_ROWS, ((b + 1) * BAND_ROWS).min(h));
+                let mut band = Vec::with_capacity(((y1 - y0) * w) as usize);
+                for y in y0..y1 {
+                    band.extend((0..w).map(|x| frame.trace_primary(scene, frame.pre.eye, primary_dir(&frame.pre, x, y, w, h))));
+                }
+                done.lock().unwrap()[b as usize] = band;
+            });
+        }
+    });
+    done.into_inner().unwrap().concat()
+}
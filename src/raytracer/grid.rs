@@ -0,0 +1,675 @@
+use raylib::core::math::Vector3;
+
+use crate::geometry::{Hit, Surface, WorldXform};
+use crate::world::{Block, BlockKind, Materials};
+
+use super::boxes::shaped_hit;
+use super::plants::plant_hit;
+use super::sample::sample_block_linear_alpha;
+
+// ====== Rejilla de voxeles en ladrillos (derivada de scene.blocks) ======
+/// Celdas por lado de un ladrillo
+pub const BRICK: i32 = 8;
+const BRICK_SHIFT: i32 = 3;
+const BRICK_CELLS: usize = (BRICK * BRICK * BRICK) as usize;
+/// En `BrickSlot`: no hay nada guardado para ese ladrillo
+const EMPTY: u32 = u32::MAX;
+
+/// Hasta dónde se mide la distancia al bloque más cercano (más = saltos más largos, build más caro)
+const DIST_CAP: u8 = 8;
+/// Lo mismo entre ladrillos (en ladrillos): un ladrillo vacío lejos de todo deja saltar ~60 celdas
+const BRICK_DIST_CAP: u8 = 8;
+/// Niveles de detalle además de la rejilla fina (celdas de 2³ y 4³)
+pub const MIP_LEVELS: usize = 2;
+
+type Cells = [u8; BRICK_CELLS]; // un byte por celda, en orden y, z, x como la rejilla
+
+/// Qué se guarda de un ladrillo: sus celdas si tiene bloques y la distancia de cada
+/// celda a lo ocupado si hay bloques a un ladrillo o menos (si no, pasa de DIST_CAP)
+#[derive(Clone, Copy, PartialEq)]
+struct BrickSlot {
+    cells: u32, // índice en `Grid::cells` (EMPTY = sin bloques)
+    dist: u32,  // índice en `Grid::dist` (EMPTY = todo lejos)
+}
+
+/// Rejilla rala: una tabla de ladrillos de 8³ donde solo los que tienen bloques
+/// guardan sus celdas. De los vacíos lejos de todo solo se sabe a cuántos ladrillos
+/// está el ocupado más cercano, que alcanza para que el DDA los cruce de un salto.
+#[derive(Clone, PartialEq)]
+pub struct Grid {
+    pub w: i32, pub h: i32, pub d: i32,
+    pub min: Vector3,            // esquina mínima (borde), tamaño celda=1
+    nb: [i32; 3],                // ladrillos por eje
+    bricks: Vec<BrickSlot>,
+    brick_dist: Vec<u8>,         // ladrillos hasta el ocupado más cercano (Chebyshev, hasta BRICK_DIST_CAP)
+    cells: Vec<Cells>,           // 0=vacío; 1..=tipo (k+1)
+    dist: Vec<Cells>,            // celdas hasta lo ocupado más cercano (Chebyshev, hasta DIST_CAP)
+    pub level: u8,               // nivel de detalle: 0 = celdas de 1, n = celdas de 2ⁿ (en coordenadas / 2ⁿ)
+    pub mips: Vec<Grid>,         // niveles 1..=MIP_LEVELS (solo en la de nivel 0)
+}
+
+/// Cuánta memoria usa la rejilla (y cuánto usaría guardada entera)
+#[derive(Clone, Copy, Debug)]
+pub struct GridStats {
+    pub size: [i32; 3],
+    pub bricks: usize,
+    pub occupied: usize,
+    pub bytes: usize,
+    pub dense_bytes: usize, // celdas + distancias, un byte cada una, sin ladrillos
+    pub mip_bytes: usize,   // los niveles de detalle
+}
+
+impl GridStats {
+    pub fn label(&self) -> String {
+        let mb = |b: usize| b as f64 / (1024.0 * 1024.0);
+        format!(
+            "{}x{}x{} celdas, {} de {} ladrillos con bloques, {:.1} MB (entera: {:.1} MB) + {:.1} MB de niveles de detalle",
+            self.size[0], self.size[1], self.size[2], self.occupied, self.bricks,
+            mb(self.bytes), mb(self.dense_bytes), mb(self.mip_bytes),
+        )
+    }
+}
+
+#[inline] pub fn kind_to_u8(k: BlockKind) -> u8 {
+    match k {
+        BlockKind::Grass => 1,
+        BlockKind::Dirt  => 2,
+        BlockKind::Stone => 3,
+        BlockKind::Log   => 4,
+        BlockKind::Leaves=> 5,
+        BlockKind::Water => 6,
+        BlockKind::Lamp  => 7,
+        BlockKind::Slab  => 8,
+        BlockKind::Stairs=> 9,
+        BlockKind::Fence => 10,
+        BlockKind::Pane  => 11,
+        BlockKind::TallGrass => 12,
+        BlockKind::Flower => 13,
+        BlockKind::Sapling => 14,
+    }
+}
+#[inline] pub fn u8_to_kind(v: u8) -> Option<BlockKind> {
+    match v {
+        1 => Some(BlockKind::Grass),
+        2 => Some(BlockKind::Dirt),
+        3 => Some(BlockKind::Stone),
+        4 => Some(BlockKind::Log),
+        5 => Some(BlockKind::Leaves),
+        6 => Some(BlockKind::Water),
+        7 => Some(BlockKind::Lamp),
+        8 => Some(BlockKind::Slab),
+        9 => Some(BlockKind::Stairs),
+        10 => Some(BlockKind::Fence),
+        11 => Some(BlockKind::Pane),
+        12 => Some(BlockKind::TallGrass),
+        13 => Some(BlockKind::Flower),
+        14 => Some(BlockKind::Sapling),
+        _ => None,
+    }
+}
+
+/// Índice de la celda dentro de su ladrillo (mismo orden y, z, x que la tabla)
+#[inline] pub fn local_index(x:i32, y:i32, z:i32) -> usize {
+    let m = BRICK - 1;
+    (((y & m) << (2 * BRICK_SHIFT)) | ((z & m) << BRICK_SHIFT) | (x & m)) as usize
+}
+
+/// Cota de la distancia de una celda de un ladrillo vacío a lo ocupado, sabiendo que
+/// el ladrillo ocupado más cercano está a `r` ladrillos: hay que cruzar r-1 ladrillos
+/// enteros y además salir del propio por el lado más cercano.
+#[inline]
+fn far_cell_dist(r: u8, x:i32, y:i32, z:i32) -> u8 {
+    let m = BRICK - 1;
+    let edge = [x & m, y & m, z & m].into_iter().map(|l| l.min(m - l)).min().unwrap_or(0);
+    ((r as i32 - 1) * BRICK + 1 + edge).min(u8::MAX as i32) as u8
+}
+
+impl Grid {
+    /// Índice del ladrillo que contiene la celda
+    #[inline]
+    pub fn brick_index(&self, x:i32, y:i32, z:i32) -> usize {
+        let [nx, _, nz] = self.nb;
+        (((y >> BRICK_SHIFT) * nz + (z >> BRICK_SHIFT)) * nx + (x >> BRICK_SHIFT)) as usize
+    }
+
+    /// El ladrillo vecino de `bi` un paso hacia `dir` (±1) en el eje `axis`
+    fn neighbour(&self, bi: usize, axis: usize, dir: i32) -> Option<usize> {
+        let [nx, ny, nz] = self.nb;
+        let bi = bi as i32;
+        let mut c = [bi % nx, bi / (nx * nz), (bi / nx) % nz];
+        c[axis] += dir;
+        let inside = (0..3).all(|a| c[a] >= 0 && c[a] < [nx, ny, nz][a]);
+        inside.then(|| ((c[1] * nz + c[2]) * nx + c[0]) as usize)
+    }
+
+    /// Ladrillo y celda dentro de él (`None` fuera de la rejilla)
+    #[inline]
+    pub fn locate(&self, [x, y, z]: [i32; 3]) -> Option<(usize, usize)> {
+        if x<0 || x>=self.w || y<0 || y>=self.h || z<0 || z>=self.d { return None; }
+        Some((self.brick_index(x, y, z), local_index(x, y, z)))
+    }
+
+    /// Contenido de la celda `local` del ladrillo `bi` (0 = vacía)
+    #[inline]
+    pub fn brick_cell(&self, bi: usize, local: usize) -> u8 {
+        match self.bricks[bi].cells {
+            EMPTY => 0,
+            p => self.cells[p as usize][local],
+        }
+    }
+
+    /// A cuántas celdas, como mínimo, está lo ocupado de la celda `cell`
+    /// (que es la `local` del ladrillo `bi`)
+    #[inline]
+    pub fn brick_dist(&self, bi: usize, local: usize, [x, y, z]: [i32; 3]) -> u8 {
+        match self.bricks[bi].dist {
+            EMPTY => far_cell_dist(self.brick_dist[bi], x, y, z),
+            p => self.dist[p as usize][local],
+        }
+    }
+
+    /// Tipo de bloque en la celda (fuera de la rejilla = aire)
+    #[inline]
+    pub fn get(&self, x:i32, y:i32, z:i32) -> Option<BlockKind> {
+        self.locate([x, y, z]).and_then(|(bi, li)| u8_to_kind(self.brick_cell(bi, li)))
+    }
+
+    /// Celda que contiene el punto `p` (mundo)
+    #[inline]
+    pub fn cell_of(&self, p: Vector3) -> (i32, i32, i32) {
+        let q = p - self.min;
+        (q.x.floor() as i32, q.y.floor() as i32, q.z.floor() as i32)
+    }
+
+    pub fn stats(&self) -> GridStats {
+        let cells = self.w as usize * self.h as usize * self.d as usize;
+        GridStats {
+            size: [self.w, self.h, self.d],
+            bricks: self.bricks.len(),
+            occupied: self.cells.len(),
+            bytes: self.bytes(),
+            dense_bytes: cells * 2,
+            mip_bytes: self.mips.iter().map(Grid::bytes).sum(),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.bricks.len() * (size_of::<BrickSlot>() + 1) + (self.cells.len() + self.dist.len()) * BRICK_CELLS
+    }
+}
+
+pub fn build_grid(blocks:&[Block]) -> Grid {
+    // X/Z: los centros caen a media celda. Y puede tener offset.
+    // Mínimo 16x16 alrededor del múltiplo de 16 más cercano al centro de los bloques
+    // (0 para las capas y las islas; otro si se cargaron chunks lejos del origen);
+    // si hay bloques más lejos la rejilla crece (parejo a los dos lados).
+    // Hallar rangos Y enteros a partir de blocks:
+    let mut miny = f32::INFINITY;
+    let mut maxy = -f32::INFINITY;
+    for b in blocks {
+        miny = miny.min(b.center.y);
+        maxy = maxy.max(b.center.y);
+    }
+    if blocks.is_empty() { (miny, maxy) = (0.5, 0.5); } // p.ej. chunks todavía sin cargar
+    // capa inferior/ superior como centros
+    let y0 = (miny - 0.5).floor() + 0.5;
+    let y1 = (maxy + 0.5).ceil() - 0.5;
+    let h = (y1 - y0 + 1.0).max(1.0) as i32;
+
+    let mid = |f: fn(&Block) -> f32| {
+        let (lo, hi) = blocks.iter().map(f).fold((f32::INFINITY, -f32::INFINITY), |(lo, hi), c| (lo.min(c), hi.max(c)));
+        if lo > hi { 0.0 } else { ((lo + hi) / 32.0).round() * 16.0 }
+    };
+    let (mx, mz) = (mid(|b| b.center.x), mid(|b| b.center.z));
+    let half = |f: &dyn Fn(&Block) -> f32| blocks.iter().map(|b| f(b).abs() + 0.5).fold(8.0f32, f32::max).ceil() as i32;
+    let w = half(&|b| b.center.x - mx) * 2;
+    let d = half(&|b| b.center.z - mz) * 2;
+    let min = Vector3::new(mx - (w as f32)*0.5, y0-0.5, mz - (d as f32)*0.5);
+    // mapear blocks -> celdas (solo superficie; perfecto para primario+sombra)
+    let cells = blocks.iter().map(|b| {
+        let ix = ((b.center.x - min.x).floor() as i32).clamp(0, w-1);
+        let iy = ((b.center.y - y0).round() as i32).clamp(0, h-1);
+        let iz = ((b.center.z - min.z).floor() as i32).clamp(0, d-1);
+        ([ix, iy, iz], kind_to_u8(b.kind))
+    });
+    let mut g = grid_from_cells([w, h, d], min, 0, cells);
+    // niveles de detalle, cada uno 2x más grueso que el anterior
+    for _ in 0..MIP_LEVELS {
+        let m = downsample(g.mips.last().unwrap_or(&g));
+        g.mips.push(m);
+    }
+    g
+}
+
+/// Rejilla de `size` celdas con las celdas ocupadas dadas (tipo como en `kind_to_u8`;
+/// si una celda sale dos veces queda la última) y sus distancias
+fn grid_from_cells([w, h, d]: [i32; 3], min: Vector3, level: u8, cells: impl Iterator<Item = ([i32; 3], u8)>) -> Grid {
+    let nb = [w, h, d].map(|n| (n + BRICK - 1) >> BRICK_SHIFT);
+    let mut g = Grid {
+        w, h, d, min, nb, level,
+        bricks: vec![BrickSlot { cells: EMPTY, dist: EMPTY }; (nb[0] * nb[1] * nb[2]) as usize],
+        brick_dist: Vec::new(),
+        cells: Vec::new(),
+        dist: Vec::new(),
+        mips: Vec::new(),
+    };
+
+    // los ladrillos se van creando cuando les cae el primer bloque
+    for ([ix, iy, iz], v) in cells {
+        let bi = g.brick_index(ix, iy, iz);
+        let slot = &mut g.bricks[bi];
+        if slot.cells == EMPTY {
+            slot.cells = g.cells.len() as u32;
+            g.cells.push([0; BRICK_CELLS]);
+        }
+        g.cells[slot.cells as usize][local_index(ix, iy, iz)] = v;
+    }
+
+    // distancias entre ladrillos y, en los que tienen bloques a un ladrillo o menos, entre celdas
+    let occ: Vec<u8> = g.bricks.iter().map(|s| (s.cells != EMPTY) as u8).collect();
+    g.brick_dist = distance_field(nb, &occ, BRICK_DIST_CAP);
+    let near: Vec<usize> = (0..g.bricks.len()).filter(|&bi| g.brick_dist[bi] <= 1).collect();
+    for (i, &bi) in near.iter().enumerate() { g.bricks[bi].dist = i as u32; }
+    g.dist = near_distances(&g, &near);
+    g
+}
+
+/// El nivel siguiente de `f`: celdas de 2³, ocupadas si alguna de las ocho lo está.
+/// No se guarda qué fracción está ocupada ni se decide por mayoría porque la rejilla
+/// solo tiene la superficie (es un cascarón): un piso plano llena 4 de las 8 celdas
+/// de una gruesa y 16 de 64 en el nivel 2, y con un umbral se abrirían hoyos en el
+/// terreno. Con "alguna" la silueta crece a lo más una celda gruesa, que en la
+/// distancia desde donde se usa cada nivel (ver `lod_distances`) mide un pixel o menos.
+/// Se queda con el tipo de la de más arriba, que es la que se ve de lejos (el pasto
+/// de una ladera, el agua sobre la tierra del fondo), y se pinta con el promedio de
+/// su textura: mezclarlo con los tipos de abajo oscurecería el pasto con la tierra
+/// que de lejos no se ve. Las plantas no cuentan: de lejos no se ven y como cubo
+/// taparían el pasto. Las coordenadas son las de `f` divididas entre 2, así el DDA
+/// corre igual que en la fina con el rayo escalado.
+fn downsample(f: &Grid) -> Grid {
+    let [nx, _, nz] = f.nb;
+    let m = BRICK - 1;
+    // las ocho celdas de una gruesa caen en el mismo ladrillo y ahí van de abajo hacia
+    // arriba (orden y, z, x): la de más arriba se escribe al final
+    let cells = f.bricks.iter().enumerate().filter(|(_, s)| s.cells != EMPTY).flat_map(|(bi, s)| {
+        let bi = bi as i32;
+        let base = [bi % nx, bi / (nx * nz), (bi / nx) % nz].map(|c| c * BRICK);
+        let data = &f.cells[s.cells as usize];
+        let keep = |v: u8| u8_to_kind(v).is_some_and(|k| !k.is_plant());
+        (0..BRICK_CELLS as i32).filter(move |&li| keep(data[li as usize])).map(move |li| {
+            let [x, y, z] = [li & m, li >> (2 * BRICK_SHIFT), (li >> BRICK_SHIFT) & m];
+            ([(base[0] + x) >> 1, (base[1] + y) >> 1, (base[2] + z) >> 1], data[li as usize])
+        })
+    });
+    grid_from_cells([f.w, f.h, f.d].map(|n| (n + 1) / 2), f.min * 0.5, f.level + 1, cells)
+}
+
+/// Distancia de Chebyshev (hasta `cap`) de cada celda de una rejilla densa [x, y, z]
+/// a la ocupada más cercana. Con la métrica del máximo se puede hacer eje por eje:
+/// en X es la distancia de toda la vida (ida y vuelta por la fila) y en Z e Y cada
+/// celda toma el mínimo de max(|salto|, distancia anterior) en una ventana de ±cap.
+fn distance_field([w, h, d]: [i32; 3], data: &[u8], cap: u8) -> Vec<u8> {
+    let (w, h, d) = (w as usize, h as usize, d as usize);
+    let mut dist = vec![cap; data.len()];
+    for (row, occ) in dist.chunks_exact_mut(w).zip(data.chunks_exact(w)) {
+        row_distance(row, occ, cap);
+    }
+    // Z dentro de cada capa y luego Y: filas enteras a la vez (el bucle en X se vectoriza)
+    for (count, stride, starts) in [(d, w, (0..h).map(|y| y * w * d).collect::<Vec<_>>()), (h, w * d, (0..d).map(|z| z * w).collect())] {
+        let mut src = vec![0u8; w * count];
+        for s in starts {
+            for (i, r) in src.chunks_exact_mut(w).enumerate() {
+                r.copy_from_slice(&dist[s + i * stride..][..w]);
+            }
+            for i in 0..count {
+                let rows = (1..cap as usize).flat_map(|k| [i.checked_sub(k), Some(i + k).filter(|&j| j < count)].map(|j| (k, j)));
+                let rows = rows.filter_map(|(k, j)| Some((k as u8, &src[j? * w..][..w])));
+                window_min(&mut dist[s + i * stride..][..w], rows);
+            }
+        }
+    }
+    dist
+}
+
+/// Distancia en X de cada celda de una fila a la ocupada más cercana (hasta `cap`)
+fn row_distance(out: &mut [u8], occ: &[u8], cap: u8) {
+    let mut run = cap;
+    for (c, &v) in out.iter_mut().zip(occ) {
+        run = if v != 0 { 0 } else { (run + 1).min(cap) };
+        *c = run;
+    }
+    let mut run = cap;
+    for (c, &v) in out.iter_mut().zip(occ).rev() {
+        run = if v != 0 { 0 } else { (run + 1).min(cap) };
+        *c = (*c).min(run);
+    }
+}
+
+/// Un paso de la pasada en Z o Y: `out` se queda con el mínimo de max(salto, fila)
+/// sobre las filas a `salto` celdas de distancia
+#[inline]
+fn window_min<'a>(out: &mut [u8], rows: impl Iterator<Item = (u8, &'a [u8])>) {
+    for (k, row) in rows {
+        for (o, &v) in out.iter_mut().zip(row) {
+            *o = (*o).min(v.max(k));
+        }
+    }
+}
+
+/// Distancias exactas (hasta DIST_CAP) de las celdas de los ladrillos `near`, eje por
+/// eje igual que `distance_field`. Como DIST_CAP no pasa de un ladrillo, cada pasada
+/// solo mira al ladrillo vecino de cada lado en su eje; los que no están en `near`
+/// no tienen nada a menos de DIST_CAP en ninguna pasada.
+fn near_distances(g: &Grid, near: &[usize]) -> Vec<Cells> {
+    let b = BRICK as usize;
+    let cap = DIST_CAP;
+    // X: la fila de tres ladrillos (anterior, este, siguiente) a partir de las celdas
+    let mut pass: Vec<Cells> = Vec::with_capacity(near.len());
+    let (mut line, mut occ) = ([0u8; 3 * BRICK as usize], [0u8; 3 * BRICK as usize]);
+    for &bi in near {
+        let row3 = [g.neighbour(bi, 0, -1), Some(bi), g.neighbour(bi, 0, 1)];
+        let mut out = [cap; BRICK_CELLS];
+        for row in (0..BRICK_CELLS).step_by(b) {
+            for (k, n) in row3.iter().enumerate() {
+                for x in 0..b { occ[k * b + x] = n.map_or(0, |n| g.brick_cell(n, row + x)); }
+            }
+            row_distance(&mut line, &occ, cap);
+            out[row..row + b].copy_from_slice(&line[b..2 * b]);
+        }
+        pass.push(out);
+    }
+    // Z y luego Y: cada fila toma las filas a ±k del eje, que pueden caer en el ladrillo vecino
+    for (axis, step) in [(2, b), (1, b * b)] {
+        let mut next = Vec::with_capacity(near.len());
+        for &bi in near {
+            let slot_of = |n: Option<usize>| n.and_then(|n| {
+                let s = g.bricks[n].dist;
+                (s != EMPTY).then(|| &pass[s as usize])
+            });
+            let around = [slot_of(g.neighbour(bi, axis, -1)), slot_of(Some(bi)), slot_of(g.neighbour(bi, axis, 1))];
+            let mut out = around[1].copied().unwrap_or([cap; BRICK_CELLS]);
+            for row in (0..BRICK_CELLS).step_by(b) {
+                let pos = (row / step) % b; // posición de la fila en el eje
+                let base = row - pos * step;
+                // fila a `off` celdas en el eje (puede ser del ladrillo de al lado)
+                let at = |off: i32| {
+                    let p = pos as i32 + off;
+                    let (n, p) = if p < 0 { (0, p + BRICK) } else if p >= BRICK { (2, p - BRICK) } else { (1, p) };
+                    around[n].map(|c| &c[base + p as usize * step..][..b])
+                };
+                let rows = (1..cap as i32).flat_map(|k| [(k, -k), (k, k)]);
+                window_min(&mut out[row..row + b], rows.filter_map(|(k, off)| Some((k as u8, at(off)?))));
+            }
+            next.push(out);
+        }
+        pass = next;
+    }
+    pass
+}
+
+// ====== Ray vs AABB (grid global) ======
+#[inline]
+pub fn ray_aabb(o:Vector3, d:Vector3, mn:Vector3, mx:Vector3) -> Option<(f32,f32)> {
+    let inv = Vector3::new(1.0/d.x, 1.0/d.y, 1.0/d.z);
+    let mut t0 = (mn - o) * inv;
+    let mut t1 = (mx - o) * inv;
+    if t0.x > t1.x { std::mem::swap(&mut t0.x, &mut t1.x); }
+    if t0.y > t1.y { std::mem::swap(&mut t0.y, &mut t1.y); }
+    if t0.z > t1.z { std::mem::swap(&mut t0.z, &mut t1.z); }
+    let tmin = t0.x.max(t0.y.max(t0.z));
+    let tmax = t1.x.min(t1.y.min(t1.z));
+    if tmax < tmin || tmax <= 1e-4 { None } else { Some((tmin.max(1e-4), tmax)) }
+}
+
+// ====== DDA traversal: primer hit en la rejilla ======
+#[derive(Clone, Copy)]
+pub struct DdaHit {
+    pub t: f32, pub p: Vector3, pub face: u8, pub n: Vector3, pub uv: [f32;2], pub kind: BlockKind,
+    pub lod: u8, // nivel de la rejilla donde pegó (0 = la fina)
+    pub inst: Option<u32>, // copia de modelo donde pegó (None = el mundo)
+    pub surface: Surface,  // bloque (de tipo `kind`) o material de una malla
+}
+
+impl DdaHit {
+    /// Adaptar a Hit para sombrear
+    #[inline]
+    pub fn to_hit(&self) -> Hit {
+        Hit { id:1, t:self.t, p:self.p, n:self.n, uv:self.uv, face:self.face, lod:self.lod, inst:self.inst, surface:self.surface }
+    }
+
+    /// Impacto de un rayo trazado en los bloques, pasado a la escena (`t`, cara y uv no cambian)
+    #[inline]
+    pub fn to_world(self, xf: &WorldXform) -> Self {
+        Self { p: xf.point_to_world(self.p), n: xf.normal_to_world(self.n), ..self }
+    }
+}
+
+/// Estado del DDA de un rayo: celda actual y `t` del siguiente plano en cada eje
+#[derive(Clone, Copy)]
+struct DdaState {
+    pub t: f32, pub t_end: f32,  // t de entrada a la celda actual / de salida de la rejilla
+    pub cell: [i32; 3],
+    pub step: [i32; 3],
+    pub tmax: [f32; 3],
+    pub tdelta: [f32; 3],
+    pub face: u8,                // cara por la que se entró (255 = primera celda)
+}
+
+impl DdaState {
+    /// Entrada del rayo a la rejilla (`None` si no la toca)
+    #[inline]
+    pub fn start(o:Vector3, d:Vector3, g:&Grid) -> Option<Self> {
+        Self::start_from(o, d, g, 0.0)
+    }
+
+    /// Como `start` pero sin mirar lo que está antes de `t_from`
+    #[inline]
+    pub fn start_from(o:Vector3, d:Vector3, g:&Grid, t_from: f32) -> Option<Self> {
+        let max = g.min + Vector3::new(g.w as f32, g.h as f32, g.d as f32);
+        let (t, t_end) = ray_aabb(o,d,g.min,max)?;
+        let t = t.max(t_from);
+        if t > t_end { return None; }
+        // punto de entrada
+        let p = o + d*t;
+
+        // índices de celda
+        let ix = ((p.x - g.min.x).floor() as i32).clamp(0, g.w-1);
+        let iy = ((p.y - g.min.y).floor() as i32).clamp(0, g.h-1);
+        let iz = ((p.z - g.min.z).floor() as i32).clamp(0, g.d-1);
+
+        // pasos y t next
+        let step = [
+            if d.x>0.0 {1} else {-1},
+            if d.y>0.0 {1} else {-1},
+            if d.z>0.0 {1} else {-1},
+        ];
+
+        let next_x = g.min.x + (if d.x>0.0 { (ix+1) as f32 } else { ix as f32 });
+        let next_y = g.min.y + (if d.y>0.0 { (iy+1) as f32 } else { iy as f32 });
+        let next_z = g.min.z + (if d.z>0.0 { (iz+1) as f32 } else { iz as f32 });
+
+        let tmax = [
+            if d.x!=0.0 {(next_x - o.x)/d.x} else { f32::INFINITY },
+            if d.y!=0.0 {(next_y - o.y)/d.y} else { f32::INFINITY },
+            if d.z!=0.0 {(next_z - o.z)/d.z} else { f32::INFINITY },
+        ];
+        let tdelta = [
+            if d.x!=0.0 { (1.0/d.x).abs() } else { f32::INFINITY },
+            if d.y!=0.0 { (1.0/d.y).abs() } else { f32::INFINITY },
+            if d.z!=0.0 { (1.0/d.z).abs() } else { f32::INFINITY },
+        ];
+
+        Some(Self { t, t_end, cell: [ix, iy, iz], step, tmax, tdelta, face: 255 })
+    }
+
+    /// Avanzar al siguiente plano
+    #[inline]
+    pub fn advance(&mut self) {
+        let [tx, ty, tz] = self.tmax;
+        let axis = if tx <= ty && tx <= tz { 0 } else if ty <= tz { 1 } else { 2 };
+        self.t = self.tmax[axis];
+        self.tmax[axis] += self.tdelta[axis];
+        self.cell[axis] += self.step[axis];
+        self.face = axis as u8 * 2 + if self.step[axis]==1 {0} else {1};
+    }
+
+    /// La celda actual está a `r` celdas de lo ocupado: todo el cubo de radio r-1 a su
+    /// alrededor está vacío, así que da de una vez los pasos que no salen de él.
+    /// Cada eje suma su `tdelta` igual que `advance`, y el orden entre ejes no cambia
+    /// esas sumas: el estado queda idéntico (bit a bit) al de ir celda por celda.
+    #[inline]
+    pub fn skip(&mut self, r: u8) {
+        if r < 2 { return; }
+        // t del paso que sale del cubo en cada eje (el r-ésimo)
+        let exit = |a: usize| (1..r).fold(self.tmax[a], |v, _| v + self.tdelta[a]);
+        let ex = [exit(0), exit(1), exit(2)];
+        // el primero en salir, con el mismo desempate que `advance` (x, luego y, luego z)
+        let ea = if ex[0] <= ex[1] && ex[0] <= ex[2] { 0 } else if ex[1] <= ex[2] { 1 } else { 2 };
+        let e = ex[ea];
+        // dar todos los pasos que `advance` daría antes de ese; `t` y la cara quedan
+        // las del último (el de mayor t; en empate el del último eje, que va después)
+        let mut last: Option<(f32, usize)> = None;
+        for a in 0..3 {
+            while self.tmax[a] < e || (a < ea && self.tmax[a] == e) {
+                let v = self.tmax[a];
+                if last.is_none_or(|(lv, _)| v >= lv) { last = Some((v, a)); }
+                self.tmax[a] += self.tdelta[a];
+                self.cell[a] += self.step[a];
+            }
+        }
+        if let Some((v, a)) = last {
+            self.t = v;
+            self.face = a as u8 * 2 + if self.step[a]==1 {0} else {1};
+        }
+    }
+}
+
+/// Primer impacto del rayo. Con `skip` las celdas vacías saltan todo el espacio
+/// libre que las rodea (ver `DdaState::skip`); sin él se va celda por celda.
+pub fn trace_grid_first(o:Vector3, d:Vector3, g:&Grid, skip: bool, mats:&Materials, is_night: bool) -> Option<DdaHit> {
+    trace_grid_from(o, d, g, DdaState::start(o, d, g)?, skip, mats, is_night)
+}
+
+/// Primer impacto de un rayo primario con nivel de detalle: celda por celda hasta
+/// `lod[0]`, desde ahí en el nivel 1 hasta `lod[1]` y después en el 2 (infinito =
+/// no pasar a ese nivel). Ver `Frame::lod`.
+pub fn trace_grid_lod(o:Vector3, d:Vector3, g:&Grid, lod: &[f32; MIP_LEVELS], skip: bool, mats:&Materials, is_night: bool) -> Option<DdaHit> {
+    let fine = DdaState::start(o, d, g).and_then(|mut s| {
+        s.t_end = s.t_end.min(lod[0]);
+        trace_grid_from(o, d, g, s, skip, mats, is_night)
+    });
+    // los niveles gruesos sobresalen un poco de la fina: se miran aunque el rayo no la toque
+    fine.or_else(|| trace_mips(o, d, g, lod, skip, mats, is_night))
+}
+
+/// Lo que sigue de `trace_grid_lod` después de la rejilla fina. En el nivel n las
+/// coordenadas están divididas entre 2ⁿ: el rayo va desde o / 2ⁿ con la misma
+/// dirección, así que su t también queda dividida entre 2ⁿ.
+fn trace_mips(o:Vector3, d:Vector3, g:&Grid, lod: &[f32; MIP_LEVELS], skip: bool, mats:&Materials, is_night: bool) -> Option<DdaHit> {
+    for (i, mip) in g.mips.iter().enumerate() {
+        let from = lod[i];
+        if from == f32::INFINITY { break; }
+        let to = lod.get(i + 1).copied().unwrap_or(f32::INFINITY);
+        let k = (2 << i) as f32;
+        let oc = o / k;
+        let Some(mut s) = DdaState::start_from(oc, d, mip, from / k) else { continue; };
+        s.t_end = s.t_end.min(to / k);
+        if let Some(mut hit) = trace_grid_from(oc, d, mip, s, skip, mats, is_night) {
+            (hit.t, hit.p) = (hit.t * k, hit.p * k);
+            return Some(hit);
+        }
+    }
+    None
+}
+
+/// Sigue el DDA desde el estado `s` (la entrada a la rejilla o a un nivel grueso)
+fn trace_grid_from(o:Vector3, d:Vector3, g:&Grid, mut s: DdaState, skip: bool, mats:&Materials, is_night: bool) -> Option<DdaHit> {
+    while s.t <= s.t_end {
+        if let Some((bi, li)) = g.locate(s.cell) {
+            // ¿ocupada? si no, saltar el espacio vacío que la rodea
+            if let Some(kind) = u8_to_kind(g.brick_cell(bi, li)) {
+                if let Some(hit) = cell_hit(o, d, g, &s, kind, mats, is_night) { return Some(hit); }
+            } else if skip {
+                s.skip(g.brick_dist(bi, li, s.cell));
+            }
+        }
+        s.advance();
+    }
+    None
+}
+
+/// Impacto con la celda ocupada donde está el DDA; `None` si el rayo pasa
+/// por un texel transparente de hojas, ventanas o plantas, o no toca las cajas de
+/// un bloque que no es un cubo, y sigue
+#[inline]
+fn cell_hit(o:Vector3, d:Vector3, g:&Grid, s:&DdaState, kind:BlockKind, mats:&Materials, is_night: bool) -> Option<DdaHit> {
+    if kind.is_plant() { return plant_hit(o, d, g, s.cell, kind, mats, is_night); }
+    let (t, [ix, iy, iz]) = (s.t, s.cell);
+    // calcular normal/UV a partir de la cara de entrada (face)
+    let n = match s.face {
+        0 => Vector3::new(-1.0,0.0,0.0),
+        1 => Vector3::new( 1.0,0.0,0.0),
+        2 => Vector3::new(0.0,-1.0,0.0),
+        3 => Vector3::new(0.0, 1.0,0.0),
+        4 => Vector3::new(0.0,0.0,-1.0),
+        5 => Vector3::new(0.0,0.0, 1.0),
+        _ => {
+            // primer voxel (t==tmin), escoger cara por min de tmax*
+            let [tmaxx, tmaxy, tmaxz] = s.tmax;
+            if tmaxx <= tmaxy && tmaxx <= tmaxz {
+                if s.step[0]==1 { Vector3::new(-1.0,0.0,0.0) } else { Vector3::new(1.0,0.0,0.0) }
+            } else if tmaxy <= tmaxz {
+                if s.step[1]==1 { Vector3::new(0.0,-1.0,0.0) } else { Vector3::new(0.0,1.0,0.0) }
+            } else {
+                if s.step[2]==1 { Vector3::new(0.0,0.0,-1.0) } else { Vector3::new(0.0,0.0,1.0) }
+            }
+        }
+    };
+    // losas, escaleras, cercas y ventanas: sus cajas dentro de la celda
+    // (en los niveles gruesos se ven como un cubo)
+    let (t, n) = if g.level == 0 && !kind.is_cube() { shaped_hit(o, d, g, [ix, iy, iz], kind)? } else { (t, n) };
+
+    // punto de impacto
+    let p = o + d*t;
+
+    // centro del voxel
+    let cx = g.min.x + ix as f32 + 0.5;
+    let cy = g.min.y + iy as f32 + 0.5;
+    let cz = g.min.z + iz as f32 + 0.5;
+
+    // UV por cara (igual que en AABB)
+    let size = 1.0;
+    let s = Vector3::new(
+        (p.x - (cx-0.5))/size,
+        (p.y - (cy-0.5))/size,
+        (p.z - (cz-0.5))/size
+    );
+    let f = face_for_normal(n);
+    let uv = match f {
+        // LADOS: v = s.y (↑)
+        0 => [1.0 - s.z, s.y], // -X
+        1 => [s.z,       s.y], // +X
+        // TOP/BOTTOM (igual que antes)
+        2 => [s.x,       s.z      ], // -Y
+        3 => [s.x,       1.0 - s.z], // +Y
+        // LADOS Z: v = s.y (↑)
+        4 => [s.x,       s.y],      // -Z
+        5 => [1.0 - s.x, s.y],      // +Z
+        _ => [0.0,0.0]
+    };
+
+    // Cutout de hojas y ventanas: si alpha baja, sigue el DDA (no es hit sólido)
+    // (en los niveles gruesos cuenta el alpha promedio: las copas se ven llenas)
+    if let BlockKind::Leaves | BlockKind::Pane = kind {
+        let (_c, a) = sample_block_linear_alpha(mats, uv, f, kind, is_night, g.level);
+        if a < 0.1 { return None; /* pasa luz/visión */ }
+    }
+    Some(DdaHit{ t, p, face:f, n, uv, kind, lod: g.level, inst: None, surface: Surface::Block })
+}
+
+#[inline]
+pub fn face_for_normal(n: Vector3) -> u8 {
+    if n.x < -0.5 { 0 } else if n.x > 0.5 { 1 }
+    else if n.y < -0.5 { 2 } else if n.y > 0.5 { 3 }
+    else if n.z < -0.5 { 4 } else { 5 }
+}
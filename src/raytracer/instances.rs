@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use raylib::core::math::Vector3;
+
+use crate::geometry::WorldXform;
+use crate::world::{self, Block, Materials, Placement};
+
+use super::grid::{build_grid, ray_aabb, trace_grid_first, DdaHit, Grid};
+
+/// Copias por hoja de la BVH (con pocas copias por hoja se prueban menos rejillas)
+const LEAF_SIZE: usize = 2;
+
+/// Un modelo de voxeles: se carga y se arma su rejilla una sola vez aunque tenga muchas copias
+pub struct Model {
+    pub name: String,
+    pub blocks: Vec<Block>,
+    pub grid: Grid,
+    lo: Vector3, hi: Vector3, // caja de sus bloques
+}
+
+impl Model {
+    pub fn new(name: String, blocks: Vec<Block>) -> Self {
+        let inf = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let (mut lo, mut hi) = (inf, -inf);
+        for b in &blocks {
+            lo = lo.min(b.center - Vector3::new(b.half, b.half, b.half));
+            hi = hi.max(b.center + Vector3::new(b.half, b.half, b.half));
+        }
+        Self { name, grid: build_grid(&blocks), blocks, lo, hi }
+    }
+
+    /// Centro de la base: el punto del modelo que se pone donde dice la copia
+    pub fn pivot(&self) -> Vector3 {
+        Vector3::new((self.lo.x + self.hi.x) * 0.5, self.lo.y, (self.lo.z + self.hi.z) * 0.5)
+    }
+}
+
+/// Una copia de un modelo: `xform` lleva sus celdas a las del mundo
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    pub model: usize,
+    pub xform: WorldXform,
+}
+
+/// Nodo de la BVH: su caja y, si `count > 0`, una hoja con `ids[first..first + count]`;
+/// si no, sus hijos son los nodos `first` y `first + 1`
+#[derive(Clone, Copy)]
+struct Node {
+    lo: Vector3, hi: Vector3,
+    first: u32,
+    count: u32,
+}
+
+/// Copias de modelos en la escena. Se trazan en dos niveles: una BVH sobre las cajas
+/// de las copias (en las celdas del mundo) y, en cada copia que toca el rayo, el
+/// rayo pasado a las celdas de su modelo por el DDA de la rejilla del modelo.
+#[derive(Default)]
+pub struct Instances {
+    pub models: Vec<Model>,
+    pub list: Vec<Instance>,
+    nodes: Vec<Node>,
+    ids: Vec<u32>, // copias en el orden de las hojas
+}
+
+impl Instances {
+    /// Carga cada modelo que nombran las copias una vez (de `models_dir/<nombre>/`)
+    /// y las pone en el mundo. Las copias de un modelo sin bloques se saltan.
+    pub fn load(models_dir: &str, placements: &[Placement]) -> Self {
+        let mut by_name: HashMap<&str, Option<usize>> = HashMap::new();
+        let mut models = Vec::new();
+        let mut list = Vec::new();
+        for p in placements {
+            let id = *by_name.entry(&p.model).or_insert_with(|| {
+                let dir = Path::new(models_dir).join(&p.model);
+                let blocks = world::load_model(&dir.to_string_lossy());
+                if blocks.is_empty() {
+                    eprintln!("WARN: El modelo {} no tiene bloques; sus copias no se ponen", dir.display());
+                    return None;
+                }
+                models.push(Model::new(p.model.clone(), blocks));
+                Some(models.len() - 1)
+            });
+            let Some(model) = id else { continue; };
+            // girar y escalar alrededor de la base y dejar la base en `pos`
+            let place = WorldXform { offset: Vector3::zero(), yaw: p.yaw, scale: p.scale };
+            let pivot = place.point_to_world(models[model].pivot());
+            list.push(Instance { model, xform: WorldXform { offset: p.pos - pivot, ..place } });
+        }
+        Self::new(models, list)
+    }
+
+    /// Arma la BVH de las copias
+    pub fn new(models: Vec<Model>, list: Vec<Instance>) -> Self {
+        let boxes: Vec<(Vector3, Vector3)> = list.iter().map(|i| placed_box(&models[i.model], &i.xform)).collect();
+        let mut s = Self { ids: (0..list.len() as u32).collect(), models, list, nodes: Vec::new() };
+        if !s.list.is_empty() {
+            s.nodes.push(Node { lo: Vector3::zero(), hi: Vector3::zero(), first: 0, count: 0 });
+            s.build(0, 0, s.list.len(), &boxes);
+        }
+        s
+    }
+
+    /// Llena el nodo `ni` con las copias `ids[first..first + count]`: hoja si son pocas y
+    /// si no, mitad y mitad por la mediana de los centros en el eje más largo
+    fn build(&mut self, ni: usize, first: usize, count: usize, boxes: &[(Vector3, Vector3)]) {
+        let ids = &mut self.ids[first..first + count];
+        let inf = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let (mut lo, mut hi, mut clo, mut chi) = (inf, -inf, inf, -inf);
+        for &id in ids.iter() {
+            let (a, b) = boxes[id as usize];
+            (lo, hi) = (lo.min(a), hi.max(b));
+            (clo, chi) = (clo.min((a + b) * 0.5), chi.max((a + b) * 0.5));
+        }
+        if count <= LEAF_SIZE {
+            self.nodes[ni] = Node { lo, hi, first: first as u32, count: count as u32 };
+            return;
+        }
+        let ext = chi - clo;
+        let axis = if ext.x >= ext.y && ext.x >= ext.z { 0 } else if ext.y >= ext.z { 1 } else { 2 };
+        let key = |id: &u32| { let (a, b) = boxes[*id as usize]; [a.x + b.x, a.y + b.y, a.z + b.z][axis] };
+        let mid = count / 2;
+        ids.select_nth_unstable_by(mid, |a, b| key(a).total_cmp(&key(b)));
+        let left = self.nodes.len();
+        self.nodes.push(Node { lo, hi, first: 0, count: 0 });
+        self.nodes.push(Node { lo, hi, first: 0, count: 0 });
+        self.nodes[ni] = Node { lo, hi, first: left as u32, count: 0 };
+        self.build(left, first, mid, boxes);
+        self.build(left + 1, first + mid, count - mid, boxes);
+    }
+
+    pub fn is_empty(&self) -> bool { self.list.is_empty() }
+
+    /// Primer impacto contra las copias antes de `t_max`, con el rayo en las celdas del mundo.
+    /// Las cajas que empiezan después del mejor impacto hasta ahora ni se abren.
+    pub fn trace(&self, o: Vector3, d: Vector3, t_max: f32, skip: bool, mats: &Materials, is_night: bool) -> Option<DdaHit> {
+        if self.nodes.is_empty() { return None; }
+        let (mut best, mut t_best) = (None, t_max);
+        let mut stack = [0u32; 64];
+        let mut sp = 1;
+        while sp > 0 {
+            sp -= 1;
+            let n = self.nodes[stack[sp] as usize];
+            if !ray_aabb(o, d, n.lo, n.hi).is_some_and(|(t0, _)| t0 < t_best) { continue; }
+            if n.count == 0 {
+                (stack[sp], stack[sp + 1]) = (n.first, n.first + 1);
+                sp += 2;
+                continue;
+            }
+            for &id in &self.ids[n.first as usize..(n.first + n.count) as usize] {
+                let inst = &self.list[id as usize];
+                let (ol, dl) = (inst.xform.point_to_local(o), inst.xform.ray_dir_to_local(d));
+                if let Some(h) = trace_grid_first(ol, dl, &self.models[inst.model].grid, skip, mats, is_night)
+                    && h.t < t_best {
+                    t_best = h.t;
+                    best = Some(DdaHit { inst: Some(id), ..h.to_world(&inst.xform) });
+                }
+            }
+        }
+        best
+    }
+
+    /// Lo más cercano entre `hit` (el mundo) y las copias
+    #[inline]
+    pub fn nearest(&self, o: Vector3, d: Vector3, hit: Option<DdaHit>, skip: bool, mats: &Materials, is_night: bool) -> Option<DdaHit> {
+        if self.is_empty() { return hit; }
+        self.trace(o, d, hit.map_or(f32::INFINITY, |h| h.t), skip, mats, is_night).or(hit)
+    }
+
+    /// Bloques de cada copia y dónde quedan en la escena con el mundo en `world`
+    pub fn placed<'a>(&'a self, world: &'a WorldXform) -> impl Iterator<Item = (&'a [Block], WorldXform)> + 'a {
+        self.list.iter().map(move |i| (&self.models[i.model].blocks[..], world.compose(&i.xform)))
+    }
+
+    /// Texto para la consola
+    pub fn label(&self) -> String {
+        let names: Vec<&str> = self.models.iter().map(|m| m.name.as_str()).collect();
+        format!("{} copias de {} modelos ({})", self.list.len(), self.models.len(), names.join(", "))
+    }
+}
+
+/// Caja (en las celdas del mundo) del modelo puesto con `xf`
+fn placed_box(m: &Model, xf: &WorldXform) -> (Vector3, Vector3) {
+    let inf = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    (0..8).fold((inf, -inf), |(lo, hi), c| {
+        let corner = Vector3::new(
+            if c & 1 == 0 { m.lo.x } else { m.hi.x },
+            if c & 2 == 0 { m.lo.y } else { m.hi.y },
+            if c & 4 == 0 { m.lo.z } else { m.hi.z },
+        );
+        let p = xf.point_to_world(corner);
+        (lo.min(p), hi.max(p))
+    })
+}
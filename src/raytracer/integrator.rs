@@ -0,0 +1,91 @@
+use raylib::core::math::Vector3;
+
+use crate::raytracer::{AoMode, DebugView, RenderMode, SceneRT};
+
+use super::ao::occlusion;
+use super::color::gamma_encode;
+use super::fog::sky_srgb;
+use super::frame::Frame;
+use super::grid::DdaHit;
+use super::pathtrace;
+use super::sampler::Sampler;
+use super::sample::sample_surface;
+use super::shade::shade_block;
+
+/// Calcula el color (sRGB) que llega por un rayo. El loop de render es el mismo
+/// para todos; solo cambia el integrador que se le pasa.
+pub trait Integrator: Sync {
+    /// `first` es el primer impacto del rayo (ya trazado o sacado del G-buffer;
+    /// `None` = cielo). `smp` ya viene iniciado en el pixel y la muestra progresiva.
+    fn radiance(&self, frame: &Frame, scene: &SceneRT, o: Vector3, d: Vector3, first: Option<&DdaHit>, smp: &mut dyn Sampler) -> Vector3;
+}
+
+/// Integrador que corresponde al modo de render de la escena
+pub fn for_mode(mode: RenderMode) -> Box<dyn Integrator> {
+    match mode {
+        RenderMode::Whitted => Box::new(Whitted),
+        RenderMode::AoOnly => Box::new(AoOnly),
+        RenderMode::Debug(view) => Box::new(DebugViewer(view)),
+        RenderMode::PathTraced { max_bounces } => Box::new(PathTracer { max_bounces }),
+    }
+}
+
+/// Sol directo + ambiente + lámparas + agua (el sombreado de siempre)
+pub struct Whitted;
+
+impl Integrator for Whitted {
+    fn radiance(&self, frame: &Frame, scene: &SceneRT, _o: Vector3, d: Vector3, first: Option<&DdaHit>, smp: &mut dyn Sampler) -> Vector3 {
+        match first {
+            Some(hh) => shade_block(frame, scene, &hh.to_hit(), hh.kind, smp),
+            None => sky_srgb(d, scene.is_night),
+        }
+    }
+}
+
+/// Solo la oclusión ambiental en gris (si el AO está apagado usa el de vóxel)
+pub struct AoOnly;
+
+impl Integrator for AoOnly {
+    fn radiance(&self, frame: &Frame, scene: &SceneRT, _o: Vector3, _d: Vector3, first: Option<&DdaHit>, smp: &mut dyn Sampler) -> Vector3 {
+        let Some(hh) = first else { return Vector3::one(); };
+        let mode = if scene.ao == AoMode::Off { AoMode::Voxel } else { scene.ao };
+        let ao = occlusion(frame, scene, &hh.to_hit(), mode, smp);
+        Vector3::new(ao, ao, ao)
+    }
+}
+
+/// Vistas de depuración del primer impacto
+pub struct DebugViewer(pub DebugView);
+
+impl Integrator for DebugViewer {
+    fn radiance(&self, frame: &Frame, scene: &SceneRT, _o: Vector3, _d: Vector3, first: Option<&DdaHit>, _smp: &mut dyn Sampler) -> Vector3 {
+        let Some(hh) = first else { return Vector3::zero(); };
+        match self.0 {
+            DebugView::Normals => (hh.n + Vector3::one()) * 0.5,
+            DebugView::Depth => {
+                // 1 = cerca, 0 = del otro lado del mundo (medido en los bloques y escalado)
+                let g = &frame.grid;
+                let size = Vector3::new(g.w as f32, g.h as f32, g.d as f32);
+                let eye = frame.xform.point_to_local(frame.pre.eye);
+                let far = ((g.min + size * 0.5 - eye).length() + size.length() * 0.5) * frame.xform.scale;
+                let v = 1.0 - (hh.t / far.max(1e-3)).min(1.0);
+                Vector3::new(v, v, v)
+            }
+            DebugView::Albedo => {
+                let (c, _a) = sample_surface(scene, hh.surface, hh.uv, hh.face, hh.kind, hh.lod);
+                gamma_encode(c)
+            }
+        }
+    }
+}
+
+/// Iluminación global difusa (ver `pathtrace`)
+pub struct PathTracer {
+    pub max_bounces: u32,
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, frame: &Frame, scene: &SceneRT, o: Vector3, d: Vector3, first: Option<&DdaHit>, smp: &mut dyn Sampler) -> Vector3 {
+        pathtrace::radiance(frame, scene, o, d, first.copied(), smp, self.max_bounces)
+    }
+}
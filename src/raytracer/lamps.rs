@@ -0,0 +1,125 @@
+use raylib::core::math::Vector3;
+
+use crate::geometry::WorldXform;
+use crate::world::Block;
+
+/// Cómo se eligen las lámparas que alumbran un punto
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LampSampling {
+    All,             // suma todas las lámparas en rango (exacto)
+    Stochastic(u32), // escoge N lámparas al azar, ponderadas por su aporte (cientos de lámparas)
+}
+
+/// Una lámpara ya extraída de los bloques (intensidad evaluada en el tiempo del frame)
+#[derive(Clone, Copy, Debug)]
+pub struct Lamp {
+    pub pos: Vector3,        // centro del bloque en la escena
+    pub xform: WorldXform,   // cómo está puesto su bloque (del mundo o de una copia)
+    pub color: Vector3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+/// Índice espacial de lámparas: rejilla uniforme con celdas del tamaño del rango.
+/// Cada celda guarda las lámparas cuyo rango la toca, así un punto solo revisa su celda.
+pub struct LampIndex {
+    lamps: Vec<Lamp>,
+    min: Vector3,
+    cell: f32,
+    nx: i32, ny: i32, nz: i32,
+    starts: Vec<u32>, // celda c -> ids[starts[c]..starts[c+1]]
+    ids: Vec<u32>,
+}
+
+impl LampIndex {
+    /// Junta una sola vez las lámparas de cada grupo de bloques (el mundo y cada copia,
+    /// con dónde queda en la escena) y las reparte en la rejilla.
+    /// `time` anima el parpadeo/pulso de cada lámpara.
+    pub fn build<'a>(parts: impl Iterator<Item = (&'a [Block], WorldXform)>, time: f32) -> Self {
+        let lamps: Vec<Lamp> = parts.enumerate()
+            .flat_map(|(k, (blocks, xf))| blocks.iter().filter_map(move |b| {
+                let lp = b.light?;
+                // cada copia parpadea a su ritmo
+                let seed = b.center.x * 0.37 + b.center.z * 0.71 + k as f32 * 3.1;
+                Some(Lamp { pos: xf.point_to_world(b.center), xform: xf, color: lp.color, intensity: lp.intensity_at(time, seed), range: lp.range })
+            }))
+            .collect();
+
+        if lamps.is_empty() {
+            return Self { lamps, min: Vector3::zero(), cell: 1.0, nx: 0, ny: 0, nz: 0, starts: vec![0], ids: Vec::new() };
+        }
+
+        // caja que cubre todas las esferas de alcance
+        let mut mn = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut mx = -mn;
+        // celda de al menos un bloque: rangos diminutos no disparan la cantidad de celdas
+        let mut cell: f32 = 1.0;
+        for l in &lamps {
+            let r = Vector3::new(l.range, l.range, l.range);
+            mn = mn.min(l.pos - r);
+            mx = mx.max(l.pos + r);
+            cell = cell.max(l.range);
+        }
+        let ext = mx - mn;
+        let nx = ((ext.x / cell).ceil() as i32).max(1);
+        let ny = ((ext.y / cell).ceil() as i32).max(1);
+        let nz = ((ext.z / cell).ceil() as i32).max(1);
+        let ncells = (nx * ny * nz) as usize;
+
+        // celdas que toca la caja de alcance de cada lámpara
+        let cell_range = |l: &Lamp| {
+            let lo = (l.pos - mn - Vector3::new(l.range, l.range, l.range)) / cell;
+            let hi = (l.pos - mn + Vector3::new(l.range, l.range, l.range)) / cell;
+            (
+                (lo.x.floor() as i32).clamp(0, nx-1)..=(hi.x.floor() as i32).clamp(0, nx-1),
+                (lo.y.floor() as i32).clamp(0, ny-1)..=(hi.y.floor() as i32).clamp(0, ny-1),
+                (lo.z.floor() as i32).clamp(0, nz-1)..=(hi.z.floor() as i32).clamp(0, nz-1),
+            )
+        };
+        let cidx = |x: i32, y: i32, z: i32| ((y * nz + z) * nx + x) as usize;
+
+        // 1) contar, 2) prefijos, 3) llenar (formato CSR)
+        let mut counts = vec![0u32; ncells];
+        for l in &lamps {
+            let (rx, ry, rz) = cell_range(l);
+            for y in ry.clone() { for z in rz.clone() { for x in rx.clone() {
+                counts[cidx(x, y, z)] += 1;
+            }}}
+        }
+        let mut starts = vec![0u32; ncells + 1];
+        for c in 0..ncells { starts[c + 1] = starts[c] + counts[c]; }
+        let mut fill = starts.clone();
+        let mut ids = vec![0u32; starts[ncells] as usize];
+        for (i, l) in lamps.iter().enumerate() {
+            let (rx, ry, rz) = cell_range(l);
+            for y in ry.clone() { for z in rz.clone() { for x in rx.clone() {
+                let c = cidx(x, y, z);
+                ids[fill[c] as usize] = i as u32;
+                fill[c] += 1;
+            }}}
+        }
+
+        Self { lamps, min: mn, cell, nx, ny, nz, starts, ids }
+    }
+
+    #[inline]
+    pub fn lamp(&self, id: u32) -> &Lamp { &self.lamps[id as usize] }
+
+    /// Lámparas cuyo rango puede llegar a `p` (hay que revisar la distancia igual)
+    #[inline]
+    pub fn candidates(&self, p: Vector3) -> &[u32] {
+        if self.lamps.is_empty() { return &[]; }
+        let q = (p - self.min) / self.cell;
+        let (x, y, z) = (q.x.floor() as i32, q.y.floor() as i32, q.z.floor() as i32);
+        if x < 0 || x >= self.nx || y < 0 || y >= self.ny || z < 0 || z >= self.nz { return &[]; }
+        let c = ((y * self.nz + z) * self.nx + x) as usize;
+        &self.ids[self.starts[c] as usize..self.starts[c + 1] as usize]
+    }
+
+    /// Lámpara cuyo bloque es la celda con centro `center` (para emisión)
+    pub fn lamp_at(&self, center: Vector3) -> Option<&Lamp> {
+        self.candidates(center).iter()
+            .map(|&id| self.lamp(id))
+            .find(|l| (l.pos - center).length() < 0.25 * l.xform.scale)
+    }
+}
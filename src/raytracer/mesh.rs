@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use image::RgbaImage;
+use raylib::core::math::Vector3;
+
+use crate::geometry::{hit_triangle, Ray, Surface, WorldXform};
+use crate::obj::{load_obj, ObjData};
+use crate::world::{BlockKind, Placement};
+
+use super::color::srgb_to_linear;
+use super::grid::DdaHit;
+use super::sample::sample_texture_linear_alpha;
+
+/// Triángulos por hoja de la BVH como máximo
+const LEAF_SIZE: usize = 4;
+/// Cajas en que se prueba partir cada eje con SAH
+const SAH_BINS: usize = 12;
+/// Costo de probar una caja relativo a probar un triángulo
+const SAH_NODE_COST: f32 = 1.0;
+
+/// Material de una malla: su textura difusa o, si no tiene, su color
+struct MeshMaterial {
+    color: Vector3, // lineal
+    texture: Option<RgbaImage>,
+}
+
+/// Un triángulo ya puesto en el mundo (en las celdas de los bloques)
+struct Tri {
+    p0: Vector3, e1: Vector3, e2: Vector3,
+    n: [Vector3; 3],    // normales de los vértices (o la de la cara)
+    uv: [[f32; 2]; 3],
+    mat: u32,
+}
+
+/// Nodo de la BVH: su caja y, si `count > 0`, una hoja con `tris[first..first + count]`;
+/// si no, sus hijos son los nodos `first` y `first + 1`
+#[derive(Clone, Copy)]
+struct Node {
+    lo: Vector3, hi: Vector3,
+    first: u32,
+    count: u32,
+}
+
+/// Mallas de triángulos (`.obj`) puestas junto a los bloques. Todas las copias se
+/// pasan a las celdas del mundo al cargarlas y van en una sola BVH armada con SAH,
+/// así se trazan con el mismo rayo que la rejilla y gana el impacto más cercano.
+#[derive(Default)]
+pub struct Meshes {
+    materials: Vec<MeshMaterial>,
+    tris: Vec<Tri>,
+    nodes: Vec<Node>,
+    copies: usize,
+    names: Vec<String>,
+}
+
+impl Meshes {
+    /// Lee cada `.obj` que nombran las copias una vez (de `models_dir/<nombre>`) y pone
+    /// sus triángulos en el mundo: la base de la malla queda en la posición de la copia,
+    /// girada y escalada a su alrededor como las copias de modelos de bloques.
+    pub fn load(models_dir: &str, placements: &[Placement]) -> Self {
+        let mut loaded: HashMap<&str, Option<(ObjData, u32)>> = HashMap::new();
+        let mut s = Self::default();
+        for p in placements {
+            let entry = loaded.entry(&p.model).or_insert_with(|| {
+                let path = Path::new(models_dir).join(&p.model);
+                match load_obj(&path) {
+                    Ok(o) if !o.tris.is_empty() => {
+                        let base = s.materials.len() as u32;
+                        s.materials.extend(o.materials.iter().map(|m| MeshMaterial {
+                            color: srgb_to_linear(m.kd),
+                            texture: m.map_kd.as_ref().and_then(|f| match image::open(f) {
+                                Ok(img) => Some(img.to_rgba8()),
+                                Err(e) => { eprintln!("WARN: textura {}: {e}; se usa Kd", f.display()); None }
+                            }),
+                        }));
+                        s.names.push(p.model.clone());
+                        Some((o, base))
+                    }
+                    Ok(_) => { eprintln!("WARN: {} no tiene caras; sus copias no se ponen", path.display()); None }
+                    Err(e) => { eprintln!("WARN: {e}"); None }
+                }
+            });
+            let Some((o, base)) = entry else { continue; };
+            s.place(o, *base, p);
+        }
+        s.build();
+        s
+    }
+
+    /// Agrega los triángulos de `o` puestos con `p` (materiales desde `base`)
+    fn place(&mut self, o: &ObjData, base: u32, p: &Placement) {
+        let (lo, hi) = o.positions.iter().fold(
+            (Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY), Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)),
+            |(lo, hi), &v| (lo.min(v), hi.max(v)),
+        );
+        let place = WorldXform { offset: Vector3::zero(), yaw: p.yaw, scale: p.scale };
+        let pivot = place.point_to_world(Vector3::new((lo.x + hi.x) * 0.5, lo.y, (lo.z + hi.z) * 0.5));
+        let xf = WorldXform { offset: p.pos - pivot, ..place };
+        for t in &o.tris {
+            let [a, b, c] = t.v.map(|i| xf.point_to_world(o.positions[i]));
+            let (e1, e2) = (b - a, c - a);
+            let face_n = e1.cross(e2);
+            if face_n.length() < 1e-12 { continue; } // sin área: nunca se golpea
+            let face_n = face_n.normalized();
+            self.tris.push(Tri {
+                p0: a, e1, e2,
+                n: t.vn.map(|i| i.map_or(face_n, |i| xf.normal_to_world(o.normals[i]).normalized())),
+                uv: t.vt.map(|i| i.map_or([0.0, 0.0], |i| o.uvs[i])),
+                mat: base + t.mat as u32,
+            });
+        }
+        self.copies += 1;
+    }
+
+    /// Arma la BVH sobre todos los triángulos
+    fn build(&mut self) {
+        if self.tris.is_empty() { return; }
+        let boxes: Vec<(Vector3, Vector3)> = self.tris.iter().map(|t| {
+            let (b, c) = (t.p0 + t.e1, t.p0 + t.e2);
+            (t.p0.min(b).min(c), t.p0.max(b).max(c))
+        }).collect();
+        let mut ids: Vec<u32> = (0..self.tris.len() as u32).collect();
+        self.nodes.push(Node { lo: Vector3::zero(), hi: Vector3::zero(), first: 0, count: 0 });
+        self.split(0, 0, self.tris.len(), &mut ids, &boxes);
+        // dejar los triángulos en el orden de las hojas
+        let mut old: Vec<Option<Tri>> = std::mem::take(&mut self.tris).into_iter().map(Some).collect();
+        self.tris = ids.iter().map(|&i| old[i as usize].take().unwrap()).collect();
+    }
+
+    /// Llena el nodo `ni` con `ids[first..first + count]`. Parte por el plano (de
+    /// SAH_BINS por eje, según los centros) que deja la menor suma de área × triángulos
+    /// a cada lado; si ninguno sale más barato que probarlos todos, queda hoja.
+    fn split(&mut self, ni: usize, first: usize, count: usize, ids: &mut [u32], boxes: &[(Vector3, Vector3)]) {
+        let inf = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let ids_here = &mut ids[first..first + count];
+        let (mut lo, mut hi, mut clo, mut chi) = (inf, -inf, inf, -inf);
+        for &id in ids_here.iter() {
+            let (a, b) = boxes[id as usize];
+            (lo, hi) = (lo.min(a), hi.max(b));
+            (clo, chi) = (clo.min((a + b) * 0.5), chi.max((a + b) * 0.5));
+        }
+        self.nodes[ni] = Node { lo, hi, first: first as u32, count: count as u32 };
+        if count <= LEAF_SIZE { return; }
+
+        let axis_of = |v: Vector3, k: usize| [v.x, v.y, v.z][k];
+        let mut best: Option<(f32, usize, f32)> = None; // costo, eje, plano
+        for k in 0..3 {
+            let (c0, c1) = (axis_of(clo, k), axis_of(chi, k));
+            if c1 - c0 < 1e-6 { continue; }
+            let bin_of = |id: u32| {
+                let (a, b) = boxes[id as usize];
+                (((axis_of((a + b) * 0.5, k) - c0) / (c1 - c0) * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+            };
+            let mut bins = [(inf, -inf, 0usize); SAH_BINS];
+            for &id in ids_here.iter() {
+                let (a, b) = boxes[id as usize];
+                let bin = &mut bins[bin_of(id)];
+                *bin = (bin.0.min(a), bin.1.max(b), bin.2 + 1);
+            }
+            // área y cuenta a la izquierda de cada plano, de izquierda a derecha y al revés
+            let mut left = [(0.0, 0usize); SAH_BINS];
+            let (mut l_lo, mut l_hi, mut l_n) = (inf, -inf, 0);
+            for i in 0..SAH_BINS - 1 {
+                (l_lo, l_hi, l_n) = (l_lo.min(bins[i].0), l_hi.max(bins[i].1), l_n + bins[i].2);
+                left[i] = (half_area(l_lo, l_hi), l_n);
+            }
+            let (mut r_lo, mut r_hi, mut r_n) = (inf, -inf, 0);
+            for i in (1..SAH_BINS).rev() {
+                (r_lo, r_hi, r_n) = (r_lo.min(bins[i].0), r_hi.max(bins[i].1), r_n + bins[i].2);
+                let (la, ln) = left[i - 1];
+                if ln == 0 || r_n == 0 { continue; }
+                let cost = la * ln as f32 + half_area(r_lo, r_hi) * r_n as f32;
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, k, c0 + (c1 - c0) * i as f32 / SAH_BINS as f32));
+                }
+            }
+        }
+        let Some((cost, axis, plane)) = best else { return; };
+        if SAH_NODE_COST * half_area(lo, hi) + cost >= half_area(lo, hi) * count as f32 { return; }
+
+        // partir en su lugar: los de centro antes del plano primero
+        let mut mid = 0;
+        for i in 0..count {
+            let (a, b) = boxes[ids_here[i] as usize];
+            if axis_of((a + b) * 0.5, axis) < plane {
+                ids_here.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == 0 || mid == count { return; }
+        let left = self.nodes.len();
+        self.nodes.push(Node { lo, hi, first: 0, count: 0 });
+        self.nodes.push(Node { lo, hi, first: 0, count: 0 });
+        self.nodes[ni] = Node { lo, hi, first: left as u32, count: 0 };
+        self.split(left, first, mid, ids, boxes);
+        self.split(left + 1, first + mid, count - mid, ids, boxes);
+    }
+
+    pub fn is_empty(&self) -> bool { self.tris.is_empty() }
+
+    /// Primer triángulo antes de `t_max`, con el rayo en las celdas del mundo.
+    /// Se baja primero al hijo más cercano y no se abren las cajas que empiezan
+    /// después del mejor impacto.
+    pub fn trace(&self, o: Vector3, d: Vector3, t_max: f32) -> Option<DdaHit> {
+        if self.nodes.is_empty() { return None; }
+        let inv = Vector3::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+        // casi todos los rayos ni pasan por la caja de todas las mallas
+        if slab(o, inv, &self.nodes[0]) >= t_max { return None; }
+        let ray = Ray { o, d };
+        let (mut best, mut t_best) = (None, t_max);
+        let mut stack = [0u32; 64];
+        let mut sp = 0;
+        let mut ni = 0u32;
+        loop {
+            let n = self.nodes[ni as usize];
+            if n.count > 0 {
+                for (k, t) in self.tris[n.first as usize..(n.first + n.count) as usize].iter().enumerate() {
+                    if let Some((th, u, v)) = hit_triangle(ray, t.p0, t.e1, t.e2) && th < t_best {
+                        t_best = th;
+                        best = Some((n.first as usize + k, u, v));
+                    }
+                }
+            } else {
+                let (a, b) = (n.first, n.first + 1);
+                let ta = slab(o, inv, &self.nodes[a as usize]);
+                let tb = slab(o, inv, &self.nodes[b as usize]);
+                let (near, far, t_near, t_far) = if ta <= tb { (a, b, ta, tb) } else { (b, a, tb, ta) };
+                if t_near < t_best {
+                    if t_far < t_best { stack[sp] = far; sp += 1; }
+                    ni = near;
+                    continue;
+                }
+            }
+            // siguiente caja pendiente que todavía puede tener algo más cerca
+            loop {
+                if sp == 0 { return best.map(|(i, u, v)| self.hit(i, o, d, t_best, u, v)); }
+                sp -= 1;
+                ni = stack[sp];
+                if slab(o, inv, &self.nodes[ni as usize]) < t_best { break; }
+            }
+        }
+    }
+
+    /// Impacto en el triángulo `i` (normal interpolada y del lado del rayo)
+    fn hit(&self, i: usize, o: Vector3, d: Vector3, t: f32, u: f32, v: f32) -> DdaHit {
+        let tri = &self.tris[i];
+        let w = 1.0 - u - v;
+        let mut n = (tri.n[0] * w + tri.n[1] * u + tri.n[2] * v).normalized();
+        // de los dos lados: la normal mira hacia donde vino el rayo
+        if n.dot(d) > 0.0 { n = -n; }
+        let uv = [0, 1].map(|k| tri.uv[0][k] * w + tri.uv[1][k] * u + tri.uv[2][k] * v);
+        // `kind` no se usa para las mallas: cuenta como un bloque opaco
+        DdaHit { t, p: o + d * t, face: 255, n, uv, kind: BlockKind::Stone, lod: 0, inst: None, surface: Surface::Mesh(tri.mat) }
+    }
+
+    /// Lo más cercano entre `hit` y las mallas
+    #[inline]
+    pub fn nearest(&self, o: Vector3, d: Vector3, hit: Option<DdaHit>) -> Option<DdaHit> {
+        if self.is_empty() { return hit; }
+        self.trace(o, d, hit.map_or(f32::INFINITY, |h| h.t)).or(hit)
+    }
+
+    /// Color lineal y alpha del material `mat` en `uv`
+    #[inline]
+    pub fn sample(&self, mat: u32, uv: [f32; 2]) -> (Vector3, f32) {
+        let m = &self.materials[mat as usize];
+        match &m.texture {
+            Some(tex) => sample_texture_linear_alpha(tex, uv),
+            None => (m.color, 1.0),
+        }
+    }
+
+    /// Texto para la consola
+    pub fn label(&self) -> String {
+        format!("{} copias de {} mallas ({}), {} triángulos, {} nodos", self.copies, self.names.len(), self.names.join(", "), self.tris.len(), self.nodes.len())
+    }
+}
+
+/// Mitad del área de la caja (para comparar costos basta)
+#[inline]
+fn half_area(lo: Vector3, hi: Vector3) -> f32 {
+    let e = hi - lo;
+    if e.x < 0.0 { return 0.0; }
+    e.x * e.y + e.y * e.z + e.z * e.x
+}
+
+/// `t` donde el rayo entra a la caja del nodo (infinito si no la toca)
+#[inline]
+fn slab(o: Vector3, inv: Vector3, n: &Node) -> f32 {
+    let (a, b) = ((n.lo - o) * inv, (n.hi - o) * inv);
+    let t0 = a.x.min(b.x).max(a.y.min(b.y)).max(a.z.min(b.z)).max(0.0);
+    let t1 = a.x.max(b.x).min(a.y.max(b.y)).min(a.z.max(b.z));
+    if t0 <= t1 { t0 } else { f32::INFINITY }
+}
@@ -1,7 +1,10 @@
+use std::sync::Arc;
 use raylib::core::math::Vector3;
 use image::RgbaImage;
 
 use crate::camera::OrbitCamRT;
+use crate::geometry::WorldXform;
+use crate::light::Light;
 use crate::world::{Block, Materials};
 
 /// Modo de reflexión para agua
@@ -12,17 +15,104 @@ pub enum WaterMode {
     ReflectOnce, // 1 rebote de reflexión (más bonito, más lento)
 }
 
+/// Oclusión ambiental de la luz ambiente
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AoMode {
+    Off,
+    Voxel,                                  // por esquina con los vecinos (barato)
+    RayTraced { radius: f32, samples: u32 }, // hemisferio trazado (converge acumulando)
+}
+
+/// Qué muestra la vista de depuración
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugView {
+    Normals,
+    Depth,
+    Albedo,
+}
+
+/// Cómo se calcula el color de cada pixel (cada modo es un `Integrator`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    Whitted,                           // sol directo + ambiente + lámparas (rápido)
+    AoOnly,                            // solo oclusión ambiental
+    Debug(DebugView),                  // normales / profundidad / albedo del primer impacto
+    PathTraced { max_bounces: u32 },   // iluminación global difusa (converge acumulando)
+}
+
+impl RenderMode {
+    /// Siguiente modo (tecla F10)
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Whitted => RenderMode::PathTraced { max_bounces: 4 },
+            RenderMode::PathTraced { .. } => RenderMode::AoOnly,
+            RenderMode::AoOnly => RenderMode::Debug(DebugView::Normals),
+            RenderMode::Debug(DebugView::Normals) => RenderMode::Debug(DebugView::Depth),
+            RenderMode::Debug(DebugView::Depth) => RenderMode::Debug(DebugView::Albedo),
+            RenderMode::Debug(DebugView::Albedo) => RenderMode::Whitted,
+        }
+    }
+
+    /// Texto para el HUD
+    pub fn label(self) -> String {
+        match self {
+            RenderMode::Whitted => "Whitted".to_string(),
+            RenderMode::AoOnly => "AO only".to_string(),
+            RenderMode::Debug(DebugView::Normals) => "normals".to_string(),
+            RenderMode::Debug(DebugView::Depth) => "depth".to_string(),
+            RenderMode::Debug(DebugView::Albedo) => "albedo".to_string(),
+            RenderMode::PathTraced { max_bounces } => format!("path traced, {} bounces", max_bounces),
+        }
+    }
+}
+
 /// Escena principal
 #[derive(Clone)]
 pub struct SceneRT {
     pub cam: OrbitCamRT,
-    pub light_pos: Vector3,
+    pub lights: Vec<Light>,     // lights[0] = sol
     pub floor_color: Vector3,   // lineal 0..1
     pub show_floor: bool,
-    pub blocks: Vec<Block>,
-    pub mats: Materials,
+    pub blocks: Arc<Vec<Block>>, // compartidos: copiar la escena no copia el mundo
+    pub grid: Arc<Grid>,          // rejilla de `blocks` (se rehace con `set_blocks`)
+    pub xform: WorldXform,        // dónde queda el mundo (se aplica a los rayos, no a los bloques)
+    pub instances: Arc<Instances>, // copias de modelos puestas en el mundo (se mueven con él)
+    pub meshes: Arc<Meshes>,      // mallas de triángulos puestas en el mundo (también se mueven con él)
+    pub shapes: Arc<Shapes>,      // esferas, toros... sueltos en el mundo (idem)
+    pub mats: Arc<Materials>,   // compartidas (el hilo de render copia la escena)
     pub water_mode: WaterMode,
     pub is_night: bool,         // nuevo: modo día/noche
+    pub lamp_sampling: LampSampling,
+    pub time: f32,              // segundos; anima el parpadeo de lámparas
+    pub shadow_samples: u32,    // rayos de sombra por luz suave y por muestra
+    pub ao: AoMode,
+    pub render_mode: RenderMode,
+    pub sampler: SamplerKind,
+    pub max_spp: Option<u32>,   // tope de muestras acumuladas (None = lo que pida el modo)
+    pub noise_threshold: f32,   // muestreo adaptativo: error relativo para dar por listo un pixel (0 = apagado)
+    pub lod: bool,              // de lejos trazar contra los niveles de detalle gruesos de la rejilla
+}
+
+impl SceneRT {
+    /// Cambia el mundo y rehace su rejilla
+    pub fn set_blocks(&mut self, blocks: Vec<Block>) {
+        self.grid = Arc::new(build_grid(&blocks));
+        self.blocks = Arc::new(blocks);
+    }
+
+    /// ¿El resultado tiene ruido que conviene acumular entre frames?
+    pub fn is_stochastic(&self) -> bool {
+        match self.render_mode {
+            RenderMode::Whitted => {
+                self.lights.iter().any(|l| l.is_soft())
+                    || matches!(self.lamp_sampling, LampSampling::Stochastic(_))
+                    || matches!(self.ao, AoMode::RayTraced { .. })
+            }
+            RenderMode::AoOnly => matches!(self.ao, AoMode::RayTraced { .. }),
+            RenderMode::Debug(_) => false,
+            RenderMode::PathTraced { .. } => true,
+        }
+    }
 }
 
 pub mod color;
@@ -30,6 +120,32 @@ mod cam;
 mod sample;
 mod shade;
 mod fog;
+mod lamps;
+mod ao;
+mod grid;
+mod boxes;
+mod plants;
+mod instances;
+mod mesh;
+mod shapes;
+mod gbuffer;
+mod frame;
+mod sampler;
+mod pathtrace;
+mod integrator;
+mod denoise;
+mod reproject;
 mod renderer;
+mod progressive;
+mod scaler;
+mod worker;
 
-pub use renderer::{render, render_mt};
+pub use grid::{build_grid, Grid};
+pub use instances::Instances;
+pub use mesh::Meshes;
+pub use shapes::{Shape, Shapes};
+pub use lamps::LampSampling;
+pub use sampler::SamplerKind;
+pub use renderer::{render, render_mt, RenderOpts};
+pub use progressive::Progressive;
+pub use worker::{Change, RenderSettings, RenderWorker};
@@ -0,0 +1,206 @@
+use raylib::core::math::Vector3;
+
+use crate::geometry::Surface;
+use crate::light::{basis, LightKind};
+use crate::world::BlockKind;
+use crate::raytracer::SceneRT;
+
+use super::color::{gamma_encode, srgb_to_linear};
+use super::fog::sky_srgb;
+use super::frame::Frame;
+use super::grid::DdaHit;
+use super::lamps::Lamp;
+use super::sample::{casts_shadow, sample_block_linear_alpha, sample_surface, surface_emission};
+use super::sampler::Sampler;
+
+// Cuánto alumbra el cielo en los rebotes difusos (ajustado para parecerse al ambiente del modo Whitted)
+const SKY_GI_SCALE: f32 = 0.4;
+// A partir de qué rebote entra la ruleta rusa
+const RR_START: u32 = 2;
+
+/// Path tracing difuso por la rejilla: rebotes con distribución coseno, ruleta rusa
+/// y estimación de evento siguiente (NEE) para las luces y las lámparas.
+/// La emisión de las lámparas solo se suma al verla directo (o tras un espejo),
+/// porque en los rebotes difusos ya la cuenta el NEE.
+/// `first` es el primer impacto, que ya viene trazado. Devuelve sRGB como `shade_block`.
+pub fn radiance(frame: &Frame, scene: &SceneRT, o: Vector3, d: Vector3, first: Option<DdaHit>, smp: &mut dyn Sampler, max_bounces: u32) -> Vector3 {
+    let mut first = Some(first);
+    let mut o = o;
+    let mut d = d;
+    let mut throughput = Vector3::one();
+    let mut l = Vector3::zero();
+    let mut count_emission = true;
+
+    let mut bounce = 0;
+    while bounce <= max_bounces {
+        let hit = match first.take() {
+            Some(h) => h,
+            None => frame.trace(scene, o, d),
+        };
+        let Some(hit) = hit else {
+            // el cielo visto directo (o en un espejo) sale igual que en Whitted;
+            // solo la luz que llega de él tras un rebote difuso se escala
+            let scale = if count_emission { 1.0 } else { SKY_GI_SCALE };
+            l += throughput * sky_radiance(d, scene.is_night) * scale;
+            break;
+        };
+        let (albedo, alpha) = sample_surface(scene, hit.surface, hit.uv, hit.face, hit.kind, hit.lod);
+        let n = hit.n;
+
+        // emisión (lámparas de noche)
+        if count_emission && scene.is_night
+            && let Some(lamp) = lamp_of_hit(frame, &hit) {
+            l += throughput * albedo * (lamp.color * lamp.intensity);
+        }
+        // las formas que brillan no se muestrean como luces: su luz se suma siempre
+        if let Some(e) = surface_emission(scene, hit.surface) {
+            l += throughput * e;
+        }
+
+        // superficies con transparencia: agua (Fresnel) y recortes de hojas
+        match hit.kind {
+            BlockKind::Water => {
+                let cos_t = (-d).dot(n).clamp(0.0, 1.0);
+                let kr = 0.02 + 0.98 * (1.0 - cos_t).powf(5.0);
+                if smp.next_1d() < kr {
+                    d = d - n * (2.0 * d.dot(n));
+                    o = hit.p + n * 1e-3;
+                    count_emission = true;
+                    bounce += 1;
+                    continue;
+                }
+                if smp.next_1d() > alpha {
+                    o = hit.p + d * 1e-3; // atraviesa
+                    continue;
+                }
+            }
+            k if (k == BlockKind::Leaves || k.is_plant()) && smp.next_1d() > alpha => {
+                o = hit.p + d * 1e-3;
+                continue;
+            }
+            _ => {}
+        }
+
+        let p = hit.p + n * 1e-3;
+        l += throughput * albedo * direct_lights(frame, scene, p, n, smp);
+        l += throughput * albedo * direct_lamps(frame, scene, p, n, smp);
+
+        if bounce == max_bounces { break; }
+
+        // rebote difuso: pdf coseno cancela coseno/π → throughput *= albedo
+        throughput *= albedo;
+        if bounce >= RR_START {
+            let q = throughput.x.max(throughput.y).max(throughput.z).clamp(0.05, 0.95);
+            if smp.next_1d() > q { break; }
+            throughput /= q;
+        }
+        let [u1, u2] = smp.next_2d();
+        d = cosine_dir(n, u1, u2);
+        o = p;
+        count_emission = false;
+        bounce += 1;
+    }
+
+    gamma_encode(Vector3::new(l.x.max(0.0), l.y.max(0.0), l.z.max(0.0)))
+}
+
+#[inline]
+fn sky_radiance(d: Vector3, is_night: bool) -> Vector3 {
+    srgb_to_linear(sky_srgb(d, is_night))
+}
+
+#[inline]
+fn cosine_dir(n: Vector3, u1: f32, u2: f32) -> Vector3 {
+    let (t, b) = basis(n);
+    let r = u1.sqrt();
+    let phi = std::f32::consts::TAU * u2;
+    let z = (1.0 - u1).max(0.0).sqrt();
+    (t * (r * phi.cos()) + b * (r * phi.sin()) + n * z).normalized()
+}
+
+/// Lámpara del bloque que se golpeó (si emite)
+#[inline]
+fn lamp_of_hit<'a>(frame: &'a Frame, hit: &DdaHit) -> Option<&'a Lamp> {
+    if hit.surface != Surface::Block { return None; }
+    frame.lamps.lamp_at(frame.cell_center(hit.p - hit.n * 1e-3, hit.inst))
+}
+
+/// NEE de las luces de la escena (misma convención que el modo Whitted: le·cos)
+fn direct_lights(frame: &Frame, scene: &SceneRT, p: Vector3, n: Vector3, smp: &mut dyn Sampler) -> Vector3 {
+    let sun_intensity = if scene.is_night { 0.1 } else { 1.0 };
+    let mut sum = Vector3::zero();
+    for light in &scene.lights {
+        let u = if light.is_soft() { smp.next_2d() } else { [0.5, 0.5] };
+        let Some(ls) = light.sample(p, u) else { continue; };
+        let cos = n.dot(ls.wi);
+        if cos <= 0.0 { continue; }
+        if occluded(frame, scene, p, ls.wi, ls.dist, smp) { continue; }
+        let k = if let LightKind::Directional { .. } = light.kind { sun_intensity } else { 1.0 };
+        sum += ls.le * (cos * k);
+    }
+    sum
+}
+
+/// NEE de lámparas: una lámpara al azar entre las que alcanzan y un punto en
+/// una de las caras del cubo que miran al punto (medida de área, BRDF albedo/π).
+/// Las caras se eligen en los ejes de su bloque (el mundo o la copia pueden estar girados y escalados).
+fn direct_lamps(frame: &Frame, scene: &SceneRT, p: Vector3, n: Vector3, smp: &mut dyn Sampler) -> Vector3 {
+    if !scene.is_night { return Vector3::zero(); }
+    let cands = frame.lamps.candidates(p);
+    if cands.is_empty() { return Vector3::zero(); }
+    let pick = ((smp.next_1d() * cands.len() as f32) as usize).min(cands.len() - 1);
+    let lamp = frame.lamps.lamp(cands[pick]);
+    if (p - lamp.pos).length() > lamp.range { return Vector3::zero(); }
+    let xf = &lamp.xform;
+    let lp = xf.point_to_local(lamp.pos);
+    let rel = xf.point_to_local(p) - lp;
+
+    // caras visibles desde p
+    let axes = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+    let rel_a = [rel.x, rel.y, rel.z];
+    let faces: Vec<Vector3> = (0..3)
+        .filter(|&i| rel_a[i].abs() > 0.5)
+        .map(|i| axes[i] * rel_a[i].signum())
+        .collect();
+    if faces.is_empty() { return Vector3::zero(); }
+    let fi = ((smp.next_1d() * faces.len() as f32) as usize).min(faces.len() - 1);
+    let nl = faces[fi];
+    let (t, b) = basis(nl);
+    let [u1, u2] = smp.next_2d();
+    let q = xf.point_to_world(lp + nl * 0.5 + t * (u1 - 0.5) + b * (u2 - 0.5));
+    let nl = xf.normal_to_world(nl);
+
+    let to = q - p;
+    let dist = to.length();
+    let wi = to / dist;
+    let cos_s = n.dot(wi);
+    let cos_l = (-wi).dot(nl);
+    if cos_s <= 0.0 || cos_l <= 0.0 { return Vector3::zero(); }
+
+    // visible si lo primero que se cruza es la propia lámpara
+    if let Some(h) = frame.trace(scene, p, wi)
+        && h.t < dist - 1e-3
+        && casts_shadow(scene, h.surface)
+        && (h.surface != Surface::Block || (frame.cell_center(h.p + wi * 1e-3, h.inst) - lamp.pos).length() > 0.25 * xf.scale) {
+        return Vector3::zero();
+    }
+
+    let pdf_area = 1.0 / (faces.len() as f32 * xf.scale * xf.scale); // cara de lado `scale`
+    let pdf_pick = 1.0 / cands.len() as f32;
+    lamp.color * (lamp.intensity * cos_s * cos_l / (dist * dist * std::f32::consts::PI * pdf_area * pdf_pick))
+}
+
+/// Rayo de sombra (hojas por alpha, el agua no tapa)
+fn occluded(frame: &Frame, scene: &SceneRT, p: Vector3, d: Vector3, dist: f32, smp: &mut dyn Sampler) -> bool {
+    match frame.trace(scene, p, d) {
+        Some(h) if h.t < dist && casts_shadow(scene, h.surface) => match h.kind {
+            BlockKind::Water => false,
+            k if k == BlockKind::Leaves || k.is_plant() => {
+                let (_c, a) = sample_block_linear_alpha(&scene.mats, h.uv, h.face, h.kind, scene.is_night, h.lod);
+                a > smp.next_1d()
+            }
+            _ => true,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,49 @@
+use raylib::core::math::Vector3;
+
+use crate::geometry::Surface;
+use crate::world::{BlockKind, Materials};
+
+use super::grid::{face_for_normal, DdaHit, Grid};
+use super::sample::sample_block_linear_alpha;
+
+const H: f32 = std::f32::consts::FRAC_1_SQRT_2;
+/// Los dos quads de una planta: normal (horizontal) y `c`, con los puntos `p` de la
+/// celda (0..1) que cumplen n·p = c
+const QUADS: [(Vector3, f32); 2] = [
+    (Vector3 { x: H, y: 0.0, z: -H }, 0.0), // x = z
+    (Vector3 { x: H, y: 0.0, z: H }, H),    // x + z = 1
+];
+
+/// Impacto con una planta (flor, pasto alto, arbolito) en la celda `cell`: dos quads
+/// verticales en las diagonales de la celda, de esquina a esquina y de piso a techo,
+/// con la misma textura en los dos y vistos de los dos lados. Se prueba el más
+/// cercano primero y, si ahí la textura es transparente (como en las hojas), el otro;
+/// `None` si el rayo pasa por los dos y sigue el DDA.
+pub fn plant_hit(o: Vector3, d: Vector3, g: &Grid, cell: [i32; 3], kind: BlockKind, mats: &Materials, is_night: bool) -> Option<DdaHit> {
+    let base = g.min + Vector3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32);
+    let q = o - base;
+    let quad = |i: usize| {
+        let (n, c) = QUADS[i];
+        let dn = n.dot(d);
+        if dn.abs() < 1e-8 { return None; }
+        let t = (c - n.dot(q)) / dn;
+        let p = q + d * t;
+        let inside = |v: f32| (-1e-5..=1.0 + 1e-5).contains(&v);
+        (t > 1e-4 && inside(p.x) && inside(p.y) && inside(p.z)).then_some((t, i, p, n))
+    };
+    let (a, b) = (quad(0), quad(1));
+    let cands = match (a, b) {
+        (Some(x), Some(y)) if y.0 < x.0 => [b, a],
+        _ => [a, b],
+    };
+
+    cands.into_iter().flatten().find_map(|(t, i, p, n)| {
+        // u a lo largo del quad (de una esquina a la otra), v hacia arriba
+        let u = if i == 0 { (p.x + p.z) * 0.5 } else { (p.x - p.z + 1.0) * 0.5 };
+        let uv = [u, p.y];
+        let n = if n.dot(d) > 0.0 { -n } else { n }; // la cara que mira al rayo
+        let face = face_for_normal(n);
+        let (_c, a) = sample_block_linear_alpha(mats, uv, face, kind, is_night, 0);
+        (a >= 0.1).then_some(DdaHit { t, p: o + d * t, face, n, uv, kind, lod: 0, inst: None, surface: Surface::Block })
+    })
+}
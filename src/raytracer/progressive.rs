@@ -0,0 +1,290 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use image::{RgbaImage, Rgba};
+use raylib::core::math::Vector3;
+
+use super::{RenderMode, SceneRT};
+use super::cam::{precompute, primary_dir};
+use super::color::{clamp01, gamma_encode};
+use super::denoise::{demodulate, denoise, luminance, AuxPixel};
+use super::gbuffer::GBuffer;
+use super::renderer::{render_mt, RenderOpts};
+use super::reproject::{reproject, History};
+
+/// Recibe las franjas de la primera muestra conforme salen (fila inicial, pixeles)
+pub type Preview = Arc<dyn Fn(u32, &RgbaImage) + Send + Sync>;
+
+/// Muestras máximas cuando la escena tiene efectos estocásticos
+pub const MAX_SPP: u32 = 64;
+/// El path tracing necesita bastantes más para limpiarse
+pub const MAX_SPP_PT: u32 = 512;
+
+/// Muestreo adaptativo: los pixeles se revisan por bloques de TILE×TILE
+const TILE: u32 = 8;
+// Antes de esto la varianza de un pixel no es de fiar (y los estratos del sampler no se completan)
+const ADAPTIVE_MIN_SPP: u32 = 8;
+// Piso del error relativo: que los pixeles casi negros no se queden muestreando para siempre
+const NOISE_FLOOR: f32 = 0.05;
+
+/// Acumulación progresiva: mientras la vista no cambia, cada frame suma una
+/// muestra más (en lineal, en f32 y sin recortar) y se muestra el promedio.
+/// Con `denoise` también se acumulan normal/profundidad/albedo y los momentos de
+/// la irradiancia, y el promedio pasa por el filtro à-trous antes de mostrarse.
+/// Con `temporal`, al mover solo la cámara se reusa lo del frame anterior
+/// (ver `camera_moved`) en vez de volver a trazar toda la imagen.
+/// Con `scene.noise_threshold` las muestras después de las primeras van solo a los
+/// bloques de pixeles que todavía tienen ruido (ver `update_active`).
+/// `cancel` corta las muestras de refinamiento a medias (la primera imagen de una
+/// vista siempre se termina, salvo con `cancel_first`: ya hay otra en pantalla)
+/// y `preview` recibe las franjas de esa primera imagen conforme van saliendo.
+pub struct Progressive {
+    w: u32, h: u32,
+    sum: Vec<Vector3>,
+    aux_sum: Vec<AuxPixel>,
+    moments: Vec<[f32; 2]>,
+    samples: u32,
+    aux_samples: u32,
+    counts: Vec<u32>,       // muestras de cada pixel (con muestreo adaptativo no todos llevan las mismas)
+    lum: Vec<[f32; 2]>,     // suma de la luminancia del color y de su cuadrado
+    active: Vec<bool>,      // pixeles que todavía reciben muestras
+    active_px: usize,
+    img: RgbaImage,
+    history: History,
+    gbuffer: GBuffer,
+    provisional: bool, // la imagen es reproyectada: al quedarse quieto se empieza de cero
+    refresh: u32,
+    pub denoise: bool,
+    pub temporal: bool,
+    pub cancel: Option<Arc<AtomicBool>>,
+    pub cancel_first: bool,
+    pub preview: Option<Preview>,
+}
+
+impl Progressive {
+    pub fn new(w: u32, h: u32) -> Self {
+        let n = (w * h) as usize;
+        Self {
+            w, h,
+            sum: vec![Vector3::zero(); n],
+            aux_sum: vec![AuxPixel::ZERO; n],
+            moments: vec![[0.0; 2]; n],
+            samples: 0,
+            aux_samples: 0,
+            counts: vec![0; n],
+            lum: vec![[0.0; 2]; n],
+            active: vec![true; n],
+            active_px: n,
+            img: RgbaImage::new(w, h),
+            history: History::new(w, h),
+            gbuffer: GBuffer::new(),
+            provisional: false,
+            refresh: 0,
+            denoise: false,
+            temporal: true,
+            cancel: None,
+            cancel_first: false,
+            preview: None,
+        }
+    }
+
+    /// Tirar lo acumulado (cambió la vista, la luz, etc.)
+    pub fn reset(&mut self, w: u32, h: u32) {
+        if (w, h) != (self.w, self.h) {
+            let old = std::mem::replace(self, Self::new(w, h));
+            (self.denoise, self.temporal, self.cancel, self.cancel_first, self.preview) =
+                (old.denoise, old.temporal, old.cancel, old.cancel_first, old.preview);
+        } else {
+            self.clear();
+            self.history.valid.fill(false);
+        }
+    }
+
+    /// Empieza a acumular de nuevo, sin tocar la historia
+    fn clear(&mut self) {
+        self.sum.fill(Vector3::zero());
+        self.aux_sum.fill(AuxPixel::ZERO);
+        self.moments.fill([0.0; 2]);
+        self.samples = 0;
+        self.aux_samples = 0;
+        self.counts.fill(0);
+        self.lum.fill([0.0; 2]);
+        self.active.fill(true);
+        self.active_px = self.active.len();
+        self.provisional = false;
+    }
+
+    /// Solo se movió la cámara: proyecta los impactos del frame anterior a la vista
+    /// nueva, reusa su color y traza únicamente los pixeles que quedaron sin dato
+    /// (más unos cuantos que se refrescan cada vez). La imagen queda provisional.
+    pub fn camera_moved(&mut self, scene: &SceneRT, w: u32, h: u32) -> &RgbaImage {
+        if !self.temporal || (w, h) != self.history.size() || self.history.is_empty() {
+            self.reset(w, h);
+            self.accumulate(scene); // primera muestra: no se cancela
+            return self.resolve();
+        }
+        let pre = precompute(&scene.cam);
+        let rp = reproject(&self.history, &pre, self.refresh);
+        self.refresh = self.refresh.wrapping_add(1);
+        self.history = rp.history;
+
+        let opts = RenderOpts { with_aux: true, radiance: true, mask: Some(&rp.retrace), ..Default::default() };
+        let (_img, aux, radiance) = render_mt(scene, w, h, 0, opts).expect("sin cancel el render no se corta");
+        self.clear();
+        for (i, &c) in radiance.iter().enumerate() {
+            if !rp.retrace[i] { continue; }
+            let (x, y) = (i as u32 % w, i as u32 / w);
+            self.history.pos[i] = pre.eye + primary_dir(&pre, x, y, w, h) * aux[i].depth;
+            self.history.valid[i] = true;
+            self.history.color[i] = c;
+        }
+        self.sum.copy_from_slice(&self.history.color);
+        self.samples = 1;
+        self.counts.fill(1);
+        self.provisional = true;
+        let color = std::mem::take(&mut self.history.color);
+        self.encode(&color);
+        self.history.color = color;
+        &self.img
+    }
+
+    pub fn samples(&self) -> u32 { self.samples }
+    /// Fracción de los pixeles que todavía reciben muestras
+    pub fn active_fraction(&self) -> f32 { self.active_px as f32 / self.active.len().max(1) as f32 }
+    /// Muestras por pixel en promedio (con muestreo adaptativo, menos que `samples`)
+    pub fn mean_spp(&self) -> f32 {
+        self.counts.iter().map(|&c| c as f64).sum::<f64>() as f32 / self.counts.len().max(1) as f32
+    }
+    pub fn size(&self) -> (u32, u32) { (self.w, self.h) }
+    /// ¿La imagen actual salió de reproyectar el frame anterior?
+    pub fn provisional(&self) -> bool { self.provisional }
+
+    /// Cuántas muestras vale la pena acumular para esta escena
+    pub fn target_samples(scene: &SceneRT) -> u32 {
+        if let Some(n) = scene.max_spp { return n.max(1); }
+        match scene.render_mode {
+            RenderMode::PathTraced { .. } => MAX_SPP_PT,
+            _ if scene.is_stochastic() => MAX_SPP,
+            _ => 1,
+        }
+    }
+
+    pub fn done(&self, scene: &SceneRT) -> bool {
+        !self.provisional && (self.samples >= Self::target_samples(scene) || self.active_px == 0)
+    }
+
+    /// Suma una muestra más sin armar la imagen (ver `resolve`).
+    /// Devuelve `false` si se canceló; la muestra a medias no cuenta.
+    pub fn accumulate(&mut self, scene: &SceneRT) -> bool {
+        if self.provisional { self.clear(); }
+        // una imagen sin ruido no se filtra (solo borraría detalle)
+        let with_aux = self.denoise && scene.is_stochastic();
+        // la primera muestra (rayo al centro del pixel) deja la historia para reproyectar
+        let keep_history = self.temporal && self.samples == 0;
+        // la primera muestra también reusa los primeros impactos si la cámara y los
+        // bloques no cambiaron (solo se movió la luz, día/noche...)
+        // (trazados igual que el render: salto de vacío de `RenderOpts`)
+        let trace = RenderOpts::default();
+        if self.samples == 0 { self.gbuffer.update(scene, self.w, self.h, &trace); }
+        let primary = (self.samples == 0).then(|| self.gbuffer.hits());
+        let first = self.samples == 0;
+        let adaptive = self.active_px < self.active.len();
+        let opts = RenderOpts {
+            with_aux: with_aux || keep_history,
+            radiance: true,
+            mask: adaptive.then_some(&self.active[..]),
+            primary,
+            cancel: if first && !self.cancel_first { None } else { self.cancel.as_deref() },
+            on_band: if first { self.preview.as_deref().map(|f| f as _) } else { None },
+            ..trace
+        };
+        let Some((_img, aux, radiance)) = render_mt(scene, self.w, self.h, self.samples, opts) else { return false; };
+        self.samples += 1;
+        let pre = keep_history.then(|| precompute(&scene.cam));
+        // se suma el color lineal de cada muestra tal cual (sin recortar ni pasar
+        // por 8 bits): solo el promedio final se cuantiza, en `encode`
+        for (i, (s, &c)) in self.sum.iter_mut().zip(&radiance).enumerate() {
+            if !self.active[i] { continue; }
+            *s += c;
+            self.counts[i] += 1;
+            let l = luminance(c);
+            self.lum[i][0] += l;
+            self.lum[i][1] += l * l;
+            if let Some(pre) = &pre {
+                let (x, y) = (i as u32 % self.w, i as u32 / self.w);
+                self.history.pos[i] = pre.eye + primary_dir(pre, x, y, self.w, self.h) * aux[i].depth;
+                self.history.valid[i] = true;
+            }
+            if with_aux {
+                let (a, acc) = (aux[i], &mut self.aux_sum[i]);
+                acc.normal += a.normal;
+                acc.depth += a.depth;
+                acc.albedo += a.albedo;
+                let l = luminance(demodulate(c, a.albedo));
+                self.moments[i][0] += l;
+                self.moments[i][1] += l * l;
+            }
+        }
+        if with_aux { self.aux_samples += 1; }
+        if scene.noise_threshold > 0.0 && self.samples >= ADAPTIVE_MIN_SPP {
+            self.update_active(scene.noise_threshold);
+        }
+        true
+    }
+
+    /// Apaga los bloques de pixeles cuyo promedio ya no tiene ruido: el error estándar
+    /// de la luminancia, relativo a su valor, debajo de `threshold` en todo el bloque.
+    /// Un bloque apagado no vuelve a prenderse (sus estadísticas ya no cambian).
+    fn update_active(&mut self, threshold: f32) {
+        let (w, h) = (self.w, self.h);
+        for ty in (0..h).step_by(TILE as usize) {
+            for tx in (0..w).step_by(TILE as usize) {
+                if !self.active[(ty * w + tx) as usize] { continue; }
+                let (x1, y1) = ((tx + TILE).min(w), (ty + TILE).min(h));
+                let tile = || (ty..y1).flat_map(move |y| (tx..x1).map(move |x| (y * w + x) as usize));
+                let noisy = tile().any(|i| {
+                    let n = self.counts[i].max(2) as f32;
+                    let (m1, m2) = (self.lum[i][0] / n, self.lum[i][1] / n);
+                    let err = ((m2 - m1 * m1).max(0.0) / (n - 1.0)).sqrt();
+                    err > threshold * (m1 + NOISE_FLOOR)
+                });
+                if !noisy {
+                    for i in tile() { self.active[i] = false; }
+                    self.active_px -= ((x1 - tx) * (y1 - ty)) as usize;
+                }
+            }
+        }
+    }
+
+    /// Promedio de lo acumulado (filtrado si corresponde), en sRGB
+    pub fn resolve(&mut self) -> &RgbaImage {
+        let inv: Vec<f32> = self.counts.iter().map(|&c| 1.0 / c.max(1) as f32).collect();
+        let mut avg: Vec<Vector3> = self.sum.iter().zip(&inv).map(|(&s, &k)| s * k).collect();
+        // solo si todas las muestras trajeron sus buffers auxiliares
+        if self.denoise && self.aux_samples > 0 && self.aux_samples == self.samples {
+            let aux_avg: Vec<AuxPixel> = self.aux_sum.iter().zip(&inv)
+                .map(|(a, &k)| AuxPixel { normal: a.normal * k, depth: a.depth * k, albedo: a.albedo * k })
+                .collect();
+            avg = denoise(self.w, self.h, &avg, &aux_avg, &self.moments, &self.counts);
+        }
+        // lo que se muestra es lo que se reusa si la cámara se mueve
+        if self.temporal {
+            self.history.color.copy_from_slice(&avg);
+        }
+        self.encode(&avg);
+        &self.img
+    }
+
+    /// Pasa el promedio lineal a la imagen en sRGB
+    fn encode(&mut self, avg: &[Vector3]) {
+        for (i, s) in avg.iter().enumerate() {
+            let c = gamma_encode(*s);
+            let (x, y) = (i as u32 % self.w, i as u32 / self.w);
+            self.img.put_pixel(x, y, Rgba([
+                (clamp01(c.x) * 255.0 + 0.5) as u8,
+                (clamp01(c.y) * 255.0 + 0.5) as u8,
+                (clamp01(c.z) * 255.0 + 0.5) as u8,
+                255,
+            ]));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use raylib::core::math::Vector3;
+
+use super::cam::CamPre;
+use super::sampler::hash_u32;
+
+/// Uno de cada tantos pixeles reusados se vuelve a trazar igual (reflejos, brillos)
+const REFRESH_EVERY: u32 = 8;
+// Un pixel reusado más lejos que sus vecinos por este margen es fondo colándose por un hueco
+const LEAK_REL: f32 = 1.05;
+const LEAK_ABS: f32 = 0.05;
+
+/// Lo que queda del último frame: dónde pegó el rayo de cada pixel y qué color salió
+pub struct History {
+    pub w: u32, h: u32,
+    pub pos: Vec<Vector3>,   // punto del primer impacto (el cielo, lejos en su dirección)
+    pub valid: Vec<bool>,
+    pub color: Vec<Vector3>, // lineal
+}
+
+impl History {
+    pub fn new(w: u32, h: u32) -> Self {
+        let n = (w * h) as usize;
+        Self { w, h, pos: vec![Vector3::zero(); n], valid: vec![false; n], color: vec![Vector3::zero(); n] }
+    }
+
+    pub fn size(&self) -> (u32, u32) { (self.w, self.h) }
+
+    pub fn is_empty(&self) -> bool { !self.valid.iter().any(|&v| v) }
+}
+
+/// Resultado de proyectar la historia a la cámara nueva
+pub struct Reprojected {
+    pub history: History,   // los huecos tapados traen color pero no cuentan como válidos
+    pub retrace: Vec<bool>, // pixeles que hay que volver a trazar
+}
+
+/// Proyecta en pantalla un punto del mundo (en pixeles, sin redondear);
+/// `None` si queda detrás de la cámara o fuera de la imagen
+#[inline]
+fn project(pre: &CamPre, p: Vector3, w: u32, h: u32) -> Option<(f32, f32, f32)> {
+    let v = p - pre.eye;
+    let z = v.dot(pre.fwd);
+    if z <= 1e-4 { return None; }
+    let px = v.dot(pre.right) / z / (pre.aspect * pre.tan_half);
+    let py = v.dot(pre.up) / z / pre.tan_half;
+    let fx = (px + 1.0) * 0.5 * w as f32;
+    let fy = (1.0 - py) * 0.5 * h as f32;
+    if fx < 0.0 || fy < 0.0 || fx >= w as f32 || fy >= h as f32 { return None; }
+    Some((fx, fy, v.length()))
+}
+
+/// Lleva cada impacto del frame anterior a su pixel en la cámara nueva (el más
+/// cercano gana) y marca para re-trazar los huecos, los pixeles donde se cuela
+/// el fondo y una fracción que rota con `frame` para que nada quede viejo.
+pub fn reproject(hist: &History, pre: &CamPre, frame: u32) -> Reprojected {
+    let (w, h) = (hist.w, hist.h);
+    let n = (w * h) as usize;
+    let mut out = History::new(w, h);
+    let mut dist = vec![f32::INFINITY; n];
+    let projected: Vec<Option<(f32, f32, f32)>> = (0..n)
+        .map(|i| if hist.valid[i] { project(pre, hist.pos[i], w, h) } else { None })
+        .collect();
+
+    // cada impacto a su pixel; el más cercano gana
+    let mut owner: Vec<Option<usize>> = vec![None; n];
+    for (i, p) in projected.iter().enumerate() {
+        let Some((fx, fy, d)) = *p else { continue; };
+        let j = (fy as u32 * w + fx as u32) as usize;
+        if d < dist[j] {
+            dist[j] = d;
+            owner[j] = Some(i);
+        }
+    }
+    // al girar, el redondeo deja huecos de un pixel entre impactos vecinos:
+    // se tapan con el color del impacto vecino que cayó a menos de un pixel del centro.
+    // Solo se muestran: no pasan a la historia, así el error no se arrastra frame a frame.
+    // Si los vecinos están a distancias muy distintas es un borde y mejor se traza.
+    let filled: Vec<(usize, usize, f32)> = (0..n).filter(|&j| owner[j].is_none()).filter_map(|j| {
+        let (x, y) = ((j as u32 % w) as f32 + 0.5, (j as u32 / w) as f32 + 0.5);
+        let (mut best, mut far) = (None, 0.0f32);
+        for k in neighbours(j, w, h) {
+            let Some(i) = owner[k] else { continue; };
+            let Some((fx, fy, d)) = projected[i] else { continue; };
+            far = far.max(d);
+            if (fx - x).abs() >= 1.0 || (fy - y).abs() >= 1.0 { continue; }
+            if best.is_none_or(|(_, _, bd)| d < bd) { best = Some((j, i, d)); }
+        }
+        best.filter(|&(_, _, d)| far <= d * LEAK_REL + LEAK_ABS)
+    }).collect();
+    for (j, &i) in owner.iter().enumerate() {
+        let Some(i) = i else { continue; };
+        out.pos[j] = hist.pos[i];
+        out.color[j] = hist.color[i];
+        out.valid[j] = true;
+    }
+    let mut covered = out.valid.clone();
+    for (j, i, d) in filled {
+        dist[j] = d;
+        out.color[j] = hist.color[i];
+        covered[j] = true;
+    }
+
+    let retrace = (0..n).map(|i| {
+        if !covered[i] { return true; }
+        if hash_u32(i as u32).wrapping_add(frame).is_multiple_of(REFRESH_EVERY) { return true; }
+        let near = neighbours(i, w, h).map(|k| dist[k]).fold(f32::INFINITY, f32::min);
+        dist[i] > near * LEAK_REL + LEAK_ABS
+    }).collect();
+
+    Reprojected { history: out, retrace }
+}
+
+/// El pixel `i` y sus vecinos 3x3 dentro de la imagen
+#[inline]
+fn neighbours(i: usize, w: u32, h: u32) -> impl Iterator<Item = usize> {
+    let (x, y) = ((i as u32 % w) as i32, (i as u32 / w) as i32);
+    (0..9).filter_map(move |k| {
+        let (xx, yy) = (x + k % 3 - 1, y + k / 3 - 1);
+        (xx >= 0 && yy >= 0 && xx < w as i32 && yy < h as i32).then(|| (yy as u32 * w + xx as u32) as usize)
+    })
+}
@@ -1,6 +1,7 @@
 use raylib::core::math::Vector3;
 use image::RgbaImage;
 
+use crate::geometry::Surface;
 use crate::world::{Materials, BlockKind};
 use crate::raytracer::SceneRT;  // necesitamos acceso a la escena para el modo noche
 use super::color::srgb_to_linear;
@@ -17,31 +18,109 @@ pub fn sample_texture_linear_alpha(tex: &RgbaImage, uv: [f32; 2]) -> (Vector3, f
     (srgb_to_linear(srgb), a)
 }
 
+/// Las texturas de `Materials` (el número es el lugar de su promedio)
+#[derive(Clone, Copy)]
+enum Tex { GrassTop, GrassSide, Dirt, Stone, LogSide, LogTop, Leaves, Water, LampOff, LampOn, Pane, TallGrass, Flower, Sapling }
+
+const TEXTURES: [Tex; 14] = [
+    Tex::GrassTop, Tex::GrassSide, Tex::Dirt, Tex::Stone, Tex::LogSide,
+    Tex::LogTop, Tex::Leaves, Tex::Water, Tex::LampOff, Tex::LampOn, Tex::Pane,
+    Tex::TallGrass, Tex::Flower, Tex::Sapling,
+];
+
 #[inline]
-pub fn sample_block_linear_alpha(
-    mats: &Materials, uv: [f32; 2], face: u8, kind: BlockKind, is_night: bool
-) -> (Vector3, f32) {
+fn texture(mats: &Materials, t: Tex) -> &RgbaImage {
+    match t {
+        Tex::GrassTop  => &mats.grass_top,
+        Tex::GrassSide => &mats.grass_side,
+        Tex::Dirt      => &mats.dirt,
+        Tex::Stone     => &mats.stone,
+        Tex::LogSide   => &mats.log_side,
+        Tex::LogTop    => &mats.log_top,
+        Tex::Leaves    => &mats.leaves,
+        Tex::Water     => &mats.water,
+        Tex::LampOff   => &mats.lamp_off,
+        Tex::LampOn    => &mats.lamp_on,
+        Tex::Pane      => &mats.pane,
+        Tex::TallGrass => &mats.tall_grass,
+        Tex::Flower    => &mats.flower,
+        Tex::Sapling   => &mats.sapling,
+    }
+}
+
+/// Qué textura lleva la cara `face` de un bloque `kind`
+#[inline]
+fn block_texture(face: u8, kind: BlockKind, is_night: bool) -> Tex {
     match kind {
         BlockKind::Grass => {
-            if face == 3      { sample_texture_linear_alpha(&mats.grass_top,  uv) }   // +Y
-            else if face == 2 { sample_texture_linear_alpha(&mats.dirt,       uv) }   // -Y
-            else              { sample_texture_linear_alpha(&mats.grass_side, uv) }   // lados
-        }
-        BlockKind::Dirt   => sample_texture_linear_alpha(&mats.dirt,      uv),
-        BlockKind::Stone  => sample_texture_linear_alpha(&mats.stone,     uv),
-        BlockKind::Log    => if face == 2 || face == 3 {
-                                sample_texture_linear_alpha(&mats.log_top,  uv)
-                             } else {
-                                sample_texture_linear_alpha(&mats.log_side, uv)
-                             },
-        BlockKind::Leaves => sample_texture_linear_alpha(&mats.leaves,    uv), // alpha
-        BlockKind::Water  => sample_texture_linear_alpha(&mats.water,     uv), // alpha
-        BlockKind::Lamp   => {
-            if is_night {
-                sample_texture_linear_alpha(&mats.lamp_on,  uv)  // lámpara encendida de noche
-            } else {
-                sample_texture_linear_alpha(&mats.lamp_off, uv)  // lámpara apagada de día
-            }
+            if face == 3      { Tex::GrassTop }   // +Y
+            else if face == 2 { Tex::Dirt }       // -Y
+            else              { Tex::GrassSide }  // lados
         }
+        BlockKind::Dirt   => Tex::Dirt,
+        BlockKind::Stone | BlockKind::Slab | BlockKind::Stairs => Tex::Stone,
+        BlockKind::Log | BlockKind::Fence => if face == 2 || face == 3 { Tex::LogTop } else { Tex::LogSide },
+        BlockKind::Leaves => Tex::Leaves, // alpha
+        BlockKind::Water  => Tex::Water,  // alpha
+        BlockKind::Pane   => Tex::Pane,   // alpha (recorte)
+        BlockKind::TallGrass => Tex::TallGrass, // alpha (recorte), igual en los dos quads
+        BlockKind::Flower => Tex::Flower,
+        BlockKind::Sapling => Tex::Sapling,
+        // lámpara encendida de noche, apagada de día
+        BlockKind::Lamp   => if is_night { Tex::LampOn } else { Tex::LampOff },
+    }
+}
+
+/// Color lineal promedio (pesado por alpha, para que las hojas no se oscurezcan con
+/// los huecos) y alpha promedio de una textura: lo que se ve de un bloque tan lejos
+/// que la textura entera cae en un pixel
+fn texture_average(tex: &RgbaImage) -> (Vector3, f32) {
+    let (mut c, mut a) = (Vector3::zero(), 0.0);
+    for p in tex.pixels() {
+        let pa = p[3] as f32 / 255.0;
+        c += srgb_to_linear(Vector3::new(p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0)) * pa;
+        a += pa;
     }
+    let n = (tex.width() * tex.height()).max(1) as f32;
+    (if a > 0.0 { c / a } else { c }, a / n)
+}
+
+/// Color lineal y alpha de un bloque. Con `lod` > 0 (impacto en un nivel de detalle
+/// grueso) da el promedio de la textura en vez del texel en `uv`.
+#[inline]
+pub fn sample_block_linear_alpha(
+    mats: &Materials, uv: [f32; 2], face: u8, kind: BlockKind, is_night: bool, lod: u8
+) -> (Vector3, f32) {
+    let t = block_texture(face, kind, is_night);
+    if lod > 0 {
+        let avg = mats.averages.get_or_init(|| TEXTURES.iter().map(|&t| texture_average(texture(mats, t))).collect());
+        return avg[t as usize];
+    }
+    sample_texture_linear_alpha(texture(mats, t), uv)
+}
+
+/// Color lineal y alpha de lo que se golpeó: la textura del bloque `kind` o el
+/// material de la malla o de la forma
+#[inline]
+pub fn sample_surface(scene: &SceneRT, surface: Surface, uv: [f32; 2], face: u8, kind: BlockKind, lod: u8) -> (Vector3, f32) {
+    match surface {
+        Surface::Block => sample_block_linear_alpha(&scene.mats, uv, face, kind, scene.is_night, lod),
+        Surface::Mesh(mat) => scene.meshes.sample(mat, uv),
+        Surface::Shape(i) => scene.shapes.sample(i),
+    }
+}
+
+/// Luz que emite lo que se golpeó (solo las formas que brillan)
+#[inline]
+pub fn surface_emission(scene: &SceneRT, surface: Surface) -> Option<Vector3> {
+    match surface {
+        Surface::Shape(i) if scene.shapes.glows(i) => Some(scene.shapes.emission(i)),
+        _ => None,
+    }
+}
+
+/// ¿Lo que se golpeó deja pasar la luz? (las formas que brillan no hacen sombra)
+#[inline]
+pub fn casts_shadow(scene: &SceneRT, surface: Surface) -> bool {
+    !matches!(surface, Surface::Shape(i) if scene.shapes.glows(i))
 }
@@ -0,0 +1,534 @@
+use std::sync::OnceLock;
+
+/// Qué secuencia de números usa el render (todas deterministas por pixel y muestra)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent, // hash por pixel/muestra/dimensión
+    Stratified,  // estratos permutados entre las muestras progresivas
+    Halton,      // bases primas + corrimiento por pixel
+    Sobol,       // Sobol 4D con scrambling de Owen (hash) y relleno por bloques
+    BlueNoise,   // máscara void-and-cluster, rotada con cada muestra
+}
+
+impl SamplerKind {
+    /// Siguiente tipo (tecla F11)
+    pub fn next(self) -> Self {
+        match self {
+            SamplerKind::Independent => SamplerKind::Stratified,
+            SamplerKind::Stratified => SamplerKind::Halton,
+            SamplerKind::Halton => SamplerKind::Sobol,
+            SamplerKind::Sobol => SamplerKind::BlueNoise,
+            SamplerKind::BlueNoise => SamplerKind::Independent,
+        }
+    }
+
+    /// Texto para el HUD
+    pub fn label(self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "Halton",
+            SamplerKind::Sobol => "Sobol (Owen)",
+            SamplerKind::BlueNoise => "blue noise",
+        }
+    }
+}
+
+/// Fuente de números aleatorios en [0,1) para un camino. Cada llamada avanza
+/// una dimensión; el mismo pixel y muestra siempre dan la misma secuencia.
+pub trait Sampler: Send {
+    /// Empieza la muestra `sample` del pixel (x, y) (vuelve a la dimensión 0)
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32);
+    fn next_1d(&mut self) -> f32;
+    fn next_2d(&mut self) -> [f32; 2];
+}
+
+/// Sampler del tipo pedido; `spp` = muestras que se van a acumular
+pub fn for_kind(kind: SamplerKind, spp: u32) -> Box<dyn Sampler> {
+    match kind {
+        SamplerKind::Independent => Box::new(Independent::default()),
+        SamplerKind::Stratified => Box::new(Stratified { spp: spp.max(1), ..Default::default() }),
+        SamplerKind::Halton => Box::new(Halton::default()),
+        SamplerKind::Sobol => Box::new(Sobol::default()),
+        SamplerKind::BlueNoise => Box::new(BlueNoise::default()),
+    }
+}
+
+// ---- hashes ----
+
+/// hash entero (PCG)
+#[inline] pub fn hash_u32(x: u32) -> u32 {
+    let s = x.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let w = ((s >> ((s >> 28) + 4)) ^ s).wrapping_mul(277_803_737);
+    (w >> 22) ^ w
+}
+#[inline] pub fn rand01(seed: u32) -> f32 { to_unit(hash_u32(seed)) }
+#[inline] fn to_unit(x: u32) -> f32 { (x >> 8) as f32 / (1u32 << 24) as f32 }
+
+/// Semilla por pixel y muestra
+#[inline]
+pub fn pixel_seed(x: u32, y: u32, sample: u32) -> u32 {
+    hash_u32(x ^ hash_u32(y ^ hash_u32(sample.wrapping_add(0x3C6E_F372))))
+}
+
+#[inline] fn hash_combine(seed: u32, v: u32) -> u32 {
+    seed ^ v.wrapping_add(seed << 6).wrapping_add(seed >> 2)
+}
+
+// ---- independiente ----
+
+#[derive(Default)]
+struct Independent { seed: u32, dim: u32 }
+
+impl Sampler for Independent {
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32) {
+        self.seed = pixel_seed(x, y, sample);
+        self.dim = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        self.dim += 1;
+        rand01(self.seed ^ hash_u32(self.dim))
+    }
+    fn next_2d(&mut self) -> [f32; 2] { [self.next_1d(), self.next_1d()] }
+}
+
+// ---- estratificado ----
+
+/// Cada dimensión reparte las `spp` muestras del pixel en estratos (una por
+/// estrato, en orden permutado) con jitter dentro del estrato.
+#[derive(Default)]
+struct Stratified { spp: u32, pixel: u32, sample: u32, dim: u32 }
+
+impl Stratified {
+    /// Estrato que le toca a esta muestra entre `n`, y el jitter dentro de él
+    fn stratum(&mut self, n: u32) -> (u32, f32) {
+        self.dim += 1;
+        let round = self.sample / n; // pasado el presupuesto: otra permutación
+        let seed = hash_u32(self.pixel ^ hash_u32(self.dim ^ hash_u32(round)));
+        let s = permute(self.sample % n, n, seed);
+        (s, rand01(seed ^ hash_u32(self.sample)))
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32) {
+        self.pixel = pixel_seed(x, y, 0);
+        self.sample = sample;
+        self.dim = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let (s, j) = self.stratum(self.spp);
+        (s as f32 + j) / self.spp as f32
+    }
+    fn next_2d(&mut self) -> [f32; 2] {
+        let sx = (self.spp as f32).sqrt().ceil() as u32;
+        let sy = self.spp.div_ceil(sx);
+        let (s, j) = self.stratum(sx * sy);
+        let j2 = rand01(hash_u32(self.pixel ^ self.dim) ^ hash_u32(self.sample ^ 0x68E3_1DA4));
+        [((s % sx) as f32 + j) / sx as f32, ((s / sx) as f32 + j2) / sy as f32]
+    }
+}
+
+/// Permutación de 0..l por hash (Kensler, "Correlated Multi-Jittered Sampling")
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    if l <= 1 { return 0; }
+    let mut w = l - 1;
+    w |= w >> 1; w |= w >> 2; w |= w >> 4; w |= w >> 8; w |= w >> 16;
+    loop {
+        i ^= p; i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8; i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1; i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11; i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2; i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2; i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l { break; }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+// ---- Halton ----
+
+static PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton con un corrimiento (Cranley-Patterson) distinto por pixel y dimensión
+/// para que los pixeles vecinos no repitan el mismo patrón.
+#[derive(Default)]
+struct Halton { pixel: u32, sample: u32, dim: u32 }
+
+#[inline]
+fn radical_inverse(base: u32, mut i: u32) -> f32 {
+    let inv = 1.0 / base as f32;
+    let (mut f, mut r) = (inv, 0.0);
+    while i > 0 {
+        r += (i % base) as f32 * f;
+        i /= base;
+        f *= inv;
+    }
+    r
+}
+
+impl Sampler for Halton {
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32) {
+        self.pixel = pixel_seed(x, y, 0);
+        self.sample = sample;
+        self.dim = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let d = self.dim;
+        self.dim += 1;
+        let shift = rand01(self.pixel ^ hash_u32(d));
+        // más allá de la tabla de primos: independiente
+        let Some(&base) = PRIMES.get(d as usize) else {
+            return rand01(shift.to_bits() ^ hash_u32(self.sample));
+        };
+        (radical_inverse(base, self.sample) + shift).fract()
+    }
+    fn next_2d(&mut self) -> [f32; 2] { [self.next_1d(), self.next_1d()] }
+}
+
+// ---- Sobol + Owen ----
+
+/// Números de dirección de las 4 primeras dimensiones de Sobol (Joe-Kuo)
+const SOBOL_DIRS: [[u32; 32]; 4] = [
+    sobol_dirs(0, 0, &[]),
+    sobol_dirs(1, 0, &[1]),
+    sobol_dirs(2, 1, &[1, 3]),
+    sobol_dirs(3, 1, &[1, 3, 1]),
+];
+
+/// `s` = grado del polinomio, `a` = coeficientes interiores, `m` = iniciales
+const fn sobol_dirs(s: usize, a: u32, m: &[u32]) -> [u32; 32] {
+    let mut v = [0u32; 32];
+    let mut k = 0;
+    while k < 32 {
+        v[k] = if s == 0 {
+            1 << (31 - k)
+        } else if k < s {
+            m[k] << (31 - k)
+        } else {
+            let mut x = v[k - s] ^ (v[k - s] >> s);
+            let mut i = 1;
+            while i < s {
+                if (a >> (s - 1 - i)) & 1 == 1 { x ^= v[k - i]; }
+                i += 1;
+            }
+            x
+        };
+        k += 1;
+    }
+    v
+}
+
+/// Tablas por byte del índice: sobol(i) = T[0][b0] ^ T[1][b1] ^ T[2][b2] ^ T[3][b3]
+/// (el índice barajado usa los 32 bits, así se evita recorrerlos uno por uno)
+static SOBOL_TABLES: [[[u32; 256]; 4]; 4] = sobol_tables();
+
+const fn sobol_tables() -> [[[u32; 256]; 4]; 4] {
+    let mut t = [[[0u32; 256]; 4]; 4];
+    let mut d = 0;
+    while d < 4 {
+        let mut byte = 0;
+        while byte < 4 {
+            let mut v = 0;
+            while v < 256 {
+                let mut x = 0;
+                let mut k = 0;
+                while k < 8 {
+                    if (v >> k) & 1 == 1 { x ^= SOBOL_DIRS[d][byte * 8 + k]; }
+                    k += 1;
+                }
+                t[d][byte][v] = x;
+                v += 1;
+            }
+            byte += 1;
+        }
+        d += 1;
+    }
+    t
+}
+
+#[inline]
+fn sobol(index: u32, dim: usize) -> u32 {
+    let t = &SOBOL_TABLES[dim];
+    t[0][(index & 0xFF) as usize]
+        ^ t[1][((index >> 8) & 0xFF) as usize]
+        ^ t[2][((index >> 16) & 0xFF) as usize]
+        ^ t[3][(index >> 24) as usize]
+}
+
+/// Scrambling de Owen anidado por hash (Burley 2020, permutación de Laine-Karras)
+#[inline]
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits().wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Sobol 4D barajado y con scrambling de Owen; las dimensiones de más se
+/// rellenan con otro bloque 4D con otra semilla (padding de Burley).
+#[derive(Default)]
+struct Sobol { pixel: u32, sample: u32, dim: u32 }
+
+impl Sobol {
+    fn value(&self, d: u32) -> f32 {
+        let seed = hash_u32(self.pixel ^ hash_u32(d / 4));
+        let index = nested_uniform_scramble(self.sample, seed);
+        let comp = (d % 4) as usize;
+        to_unit(nested_uniform_scramble(sobol(index, comp), hash_combine(seed, comp as u32)))
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32) {
+        self.pixel = pixel_seed(x, y, 0);
+        self.sample = sample;
+        self.dim = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let v = self.value(self.dim);
+        self.dim += 1;
+        v
+    }
+    fn next_2d(&mut self) -> [f32; 2] {
+        // que un par 2D no quede partido entre dos bloques
+        if self.dim % 4 == 3 { self.dim += 1; }
+        let v = [self.value(self.dim), self.value(self.dim + 1)];
+        self.dim += 2;
+        v
+    }
+}
+
+// ---- ruido azul ----
+
+const BN_SIZE: u32 = 64;
+
+/// Máscara de ruido azul (rango normalizado por pixel), generada una vez
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(BN_SIZE as usize, 1.5))
+}
+
+/// Void-and-cluster (Ulichney): ordena los pixeles de forma que cada prefijo
+/// quede lo más repartido posible. Energía = gaussiana toroidal de los puntos.
+fn void_and_cluster(n: usize, sigma: f32) -> Vec<f32> {
+    let count = n * n;
+    let mut kern = vec![0.0f32; count];
+    for dy in 0..n {
+        for dx in 0..n {
+            let (fx, fy) = (dx.min(n - dx) as f32, dy.min(n - dy) as f32);
+            kern[dy * n + dx] = (-(fx * fx + fy * fy) / (2.0 * sigma * sigma)).exp();
+        }
+    }
+    let splat = |energy: &mut [f32], p: usize, sign: f32| {
+        let (px, py) = (p % n, p / n);
+        for y in 0..n {
+            let dy = (y + n - py) % n;
+            for x in 0..n {
+                let dx = (x + n - px) % n;
+                energy[y * n + x] += sign * kern[dy * n + dx];
+            }
+        }
+    };
+    // punto más apretado (entre los 1) o hueco más grande (entre los 0)
+    let tightest = |bits: &[bool], energy: &[f32]| {
+        (0..count).filter(|&i| bits[i]).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+    let largest_void = |bits: &[bool], energy: &[f32]| {
+        (0..count).filter(|&i| !bits[i]).min_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+
+    // 1) patrón inicial: 10% de puntos al azar, relajado moviendo el más
+    //    apretado al hueco más grande hasta que ya no cambia
+    let ones0 = (count / 10).max(1);
+    let mut bits = vec![false; count];
+    let mut energy = vec![0.0f32; count];
+    let (mut placed, mut i) = (0, 0u32);
+    while placed < ones0 {
+        let p = hash_u32(i ^ 0x5EED_B1E5) as usize % count;
+        i += 1;
+        if !bits[p] {
+            bits[p] = true;
+            splat(&mut energy, p, 1.0);
+            placed += 1;
+        }
+    }
+    for _ in 0..count {
+        let t = tightest(&bits, &energy);
+        bits[t] = false;
+        splat(&mut energy, t, -1.0);
+        let v = largest_void(&bits, &energy);
+        bits[v] = true;
+        splat(&mut energy, v, 1.0);
+        if v == t { break; }
+    }
+
+    let mut rank = vec![0usize; count];
+    // 2) quitando los más apretados: rangos por debajo del patrón inicial
+    let (mut b, mut e) = (bits.clone(), energy.clone());
+    for r in (0..ones0).rev() {
+        let t = tightest(&b, &e);
+        b[t] = false;
+        splat(&mut e, t, -1.0);
+        rank[t] = r;
+    }
+    // 3) llenando huecos hasta el final (con la gaussiana, el cúmulo de ceros
+    //    más apretado es justo el hueco más grande de unos)
+    for r in ones0..count {
+        let v = largest_void(&bits, &energy);
+        bits[v] = true;
+        splat(&mut energy, v, 1.0);
+        rank[v] = r;
+    }
+    rank.into_iter().map(|r| (r as f32 + 0.5) / count as f32).collect()
+}
+
+/// Cada dimensión lee la máscara con otro desplazamiento; entre muestras el
+/// valor se rota con la razón áurea (R1/R2), así el error queda en alta frecuencia.
+#[derive(Default)]
+struct BlueNoise { x: u32, y: u32, sample: u32, dim: u32 }
+
+impl BlueNoise {
+    #[inline]
+    fn mask_at(&self, d: u32) -> f32 {
+        let o = hash_u32(d ^ 0xB10E_0015);
+        let (ox, oy) = (o % BN_SIZE, (o / BN_SIZE) % BN_SIZE);
+        let (x, y) = ((self.x + ox) % BN_SIZE, (self.y + oy) % BN_SIZE);
+        blue_noise_mask()[(y * BN_SIZE + x) as usize]
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn start_pixel(&mut self, x: u32, y: u32, sample: u32) {
+        self.x = x;
+        self.y = y;
+        self.sample = sample;
+        self.dim = 0;
+    }
+    fn next_1d(&mut self) -> f32 {
+        let v = self.mask_at(self.dim);
+        self.dim += 1;
+        (v + self.sample as f32 * 0.618_034).fract()
+    }
+    fn next_2d(&mut self) -> [f32; 2] {
+        let (a, b) = (self.mask_at(self.dim), self.mask_at(self.dim + 1));
+        self.dim += 2;
+        let s = self.sample as f32;
+        [(a + s * 0.754_877_7).fract(), (b + s * 0.569_840_3).fract()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 5] = [
+        SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton,
+        SamplerKind::Sobol, SamplerKind::BlueNoise,
+    ];
+
+    #[test]
+    fn samplers_stay_in_unit_interval() {
+        for kind in KINDS {
+            let mut smp = for_kind(kind, 16);
+            for (x, y) in [(0, 0), (7, 3), (63, 64), (1000, 517)] {
+                for sample in [0, 1, 15, 16, 255, 4096] {
+                    smp.start_pixel(x, y, sample);
+                    for _ in 0..12 {
+                        let v = smp.next_1d();
+                        assert!((0.0..1.0).contains(&v), "{kind:?}: {v}");
+                        for v in smp.next_2d() { assert!((0.0..1.0).contains(&v), "{kind:?}: {v}"); }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_pixel_and_sample_repeat() {
+        for kind in KINDS {
+            let (mut a, mut b) = (for_kind(kind, 8), for_kind(kind, 8));
+            a.start_pixel(5, 9, 3);
+            b.start_pixel(5, 9, 3);
+            for _ in 0..8 { assert_eq!(a.next_2d(), b.next_2d(), "{kind:?}"); }
+        }
+    }
+
+    #[test]
+    fn sobol_first_points_match_reference() {
+        // orden natural (sin código Gray) de las 3 primeras dimensiones de Joe-Kuo
+        let reference = [
+            [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875],
+            [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875],
+            [0.0, 0.5, 0.75, 0.25, 0.375, 0.875, 0.625, 0.125],
+        ];
+        for (dim, want) in reference.iter().enumerate() {
+            for (i, &w) in want.iter().enumerate() {
+                assert_eq!(to_unit(sobol(i as u32, dim)), w, "dim {dim}, punto {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_prefixes_are_stratified() {
+        // los primeros 2^k puntos de cada dimensión caen uno en cada intervalo de 1/2^k,
+        // también después del barajado y el scrambling de Owen
+        let strata = |values: &mut dyn Iterator<Item = f32>, n: usize| {
+            let mut seen = vec![false; n];
+            for v in values { seen[(v * n as f32) as usize] = true; }
+            seen.iter().all(|&s| s)
+        };
+        for k in 1..=8 {
+            let n = 1usize << k;
+            for dim in 0..4 {
+                assert!(strata(&mut (0..n as u32).map(|i| to_unit(sobol(i, dim))), n), "dim {dim}, 2^{k}");
+                let pixel = Sobol { pixel: pixel_seed(12, 34, 0), sample: 0, dim: 0 };
+                let mut owen = (0..n as u32).map(|s| Sobol { sample: s, ..pixel }.value(dim as u32));
+                assert!(strata(&mut owen, n), "Owen dim {dim}, 2^{k}");
+            }
+        }
+    }
+
+    #[test]
+    fn permute_is_a_bijection() {
+        for l in [1, 2, 3, 7, 16, 64, 100, 257] {
+            for p in [0, 1, 0xDEAD_BEEF, hash_u32(l)] {
+                let mut seen = vec![false; l as usize];
+                for i in 0..l {
+                    let j = permute(i, l, p);
+                    assert!(j < l && !seen[j as usize], "l {l}, p {p:#x}: {i} -> {j}");
+                    seen[j as usize] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn radical_inverse_matches_van_der_corput() {
+        assert_eq!([0, 1, 2, 3, 4].map(|i| radical_inverse(2, i)), [0.0, 0.5, 0.25, 0.75, 0.125]);
+        let third = [0, 1, 2, 3].map(|i| radical_inverse(3, i));
+        for (v, w) in third.iter().zip([0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0]) {
+            assert!((v - w).abs() < 1e-6, "{v} vs {w}");
+        }
+    }
+
+    #[test]
+    fn blue_noise_ranks_every_pixel_once() {
+        let n = 16;
+        let mask = void_and_cluster(n, 1.5);
+        let mut ranks: Vec<usize> = mask.iter().map(|&v| (v * (n * n) as f32) as usize).collect();
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..n * n).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+/// Escalas de resolución que se pueden usar al interactuar (fracción del ancho/alto).
+/// Van por escalones para que frames seguidos tengan el mismo tamaño (y se puedan reproyectar).
+const STEPS: [f32; 6] = [1.0, 0.75, 0.5, 0.375, 0.25, 0.125];
+// Se sube un escalón solo si con él se seguiría debajo de esta fracción del tiempo (si no, oscila)
+const UP_MARGIN: f32 = 0.8;
+
+/// Elige la resolución interna según cuánto tardan los frames mientras se interactúa
+pub struct ResScaler {
+    step: usize,
+    cheap_shadows: bool, // ya en la escala mínima y todavía lento: 1 rayo de sombra
+}
+
+impl ResScaler {
+    pub fn new() -> Self {
+        Self { step: 2, cheap_shadows: false }
+    }
+
+    pub fn scale(&self) -> f32 { STEPS[self.step] }
+
+    /// Tamaño interno para una imagen final de `w`×`h`
+    pub fn size(&self, w: u32, h: u32) -> (u32, u32) {
+        let s = self.scale();
+        (((w as f32 * s) as u32).max(1), ((h as f32 * s) as u32).max(1))
+    }
+
+    /// Rayos de sombra a usar mientras se interactúa
+    pub fn shadow_samples(&self, wanted: u32) -> u32 {
+        if self.cheap_shadows { 1 } else { wanted }
+    }
+
+    /// Ajusta con lo que tardó un frame completo a la escala actual
+    pub fn update(&mut self, took: Duration, budget: Duration) {
+        let ratio = took.as_secs_f32() / budget.as_secs_f32().max(1e-3);
+        if ratio > 1.0 {
+            if self.step + 1 < STEPS.len() {
+                // el costo va con el área: bajar tantos escalones como haga falta
+                let want = self.scale() / ratio.sqrt();
+                self.step = STEPS.iter().position(|&s| s <= want).unwrap_or(STEPS.len() - 1).max(self.step + 1);
+            } else {
+                self.cheap_shadows = true;
+            }
+        } else if self.cheap_shadows {
+            if ratio < UP_MARGIN * 0.5 { self.cheap_shadows = false; }
+        } else if self.step > 0 {
+            let grow = (STEPS[self.step - 1] / self.scale()).powi(2);
+            if ratio * grow < UP_MARGIN { self.step -= 1; }
+        }
+    }
+}
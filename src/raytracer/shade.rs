@@ -1,49 +1,67 @@
+use std::cell::RefCell;
+
 use raylib::core::math::Vector3;
 
-use crate::geometry::{Ray, Hit};
+use crate::geometry::Hit;
 use crate::world::BlockKind;
+use crate::light::LightKind;
 use crate::raytracer::{SceneRT, WaterMode};
 
-use super::cam::CamPre;
+use super::ao::ambient_occlusion;
 use super::color::{clamp01, gamma_encode};
-use super::sample::sample_block_linear_alpha;
+use super::frame::Frame;
+use super::grid::DdaHit;
+use super::sample::{casts_shadow, sample_block_linear_alpha, sample_surface, surface_emission};
 use super::fog::sky_srgb;
+use super::lamps::{Lamp, LampSampling};
+use super::sampler::{hash_u32, rand01, Sampler};
 
 // ---- util ----
-#[inline] fn hash01(a: f32, b: f32, c: f32) -> f32 {
-    let x = a * 127.1 + b * 311.7 + c * 74.7;
-    let mut h = (x.sin() * 43758.5453).fract();
-    if h < 0.0 { h += 1.0; }
-    h
-}
 #[inline] fn reflect(i: Vector3, n: Vector3) -> Vector3 { i - n * (2.0 * i.dot(n)) }
 #[inline] fn fresnel_schlick(cos_theta: f32, f0: f32) -> f32 { f0 + (1.0 - f0) * (1.0 - cos_theta).powf(5.0) }
 
+thread_local! {
+    /// CDF de los pesos de las lámparas candidatas (se reusa entre puntos, sin pedir memoria)
+    static LAMP_CDF: RefCell<Vec<f32>> = const { RefCell::new(Vec::new()) };
+}
+
 /// Calcula iluminación adicional de lámparas cercanas (solo de noche)
-fn calculate_lamp_light(scene: &SceneRT, hit_pos: Vector3, normal: Vector3) -> Vector3 {
+fn calculate_lamp_light(frame: &Frame, scene: &SceneRT, hit_pos: Vector3, normal: Vector3, smp: &mut dyn Sampler) -> Vector3 {
     if !scene.is_night {
         return Vector3::new(0.0, 0.0, 0.0); // Sin luz de lámparas durante el día
     }
 
+    // Solo las lámparas cuyo rango toca la celda del punto
+    let lamps = &frame.lamps;
+    let cands = lamps.candidates(hit_pos);
     let mut total_light = Vector3::new(0.0, 0.0, 0.0);
-    let lamp_range = 8.0; // Rango de iluminación de las lámparas
-    let lamp_intensity = 3.0; // Intensidad aumentada de las lámparas
 
-    // Buscar lámparas cercanas
-    for block in &scene.blocks {
-        if let BlockKind::Lamp = block.kind {
-            let lamp_pos = block.center;
-            let to_lamp = lamp_pos - hit_pos;
-            let distance = to_lamp.length();
-            
-            if distance < lamp_range && distance > 0.1 {
-                let lamp_dir = to_lamp.normalized();
-                let attenuation = 1.0 / (1.0 + 0.5 * distance + 0.1 * distance * distance);
-                let dot_factor = normal.dot(lamp_dir).max(0.0);
-                
-                // Color cálido de lámpara (naranja/amarillo)
-                let lamp_color = Vector3::new(1.0, 0.8, 0.4);
-                total_light += lamp_color * (lamp_intensity * attenuation * dot_factor);
+    match scene.lamp_sampling {
+        LampSampling::Stochastic(n) if cands.len() > n as usize => LAMP_CDF.with_borrow_mut(|cdf| {
+            // Elegir n lámparas con probabilidad proporcional a su aporte sin coseno
+            // y dividir entre la pdf (estimador sin sesgo; el sampler cambia con cada
+            // muestra, así que la lámpara elegida también y el promedio converge).
+            // Los pesos se calculan una vez en una CDF y cada elección la busca por bisección.
+            cdf.clear();
+            let mut sum_w = 0.0;
+            for &id in cands {
+                sum_w += lamp_weight(lamps.lamp(id), hit_pos);
+                cdf.push(sum_w);
+            }
+            if sum_w <= 0.0 { return; }
+            for _ in 0..n {
+                let u = smp.next_1d() * sum_w;
+                let pick = cdf.partition_point(|&c| c <= u).min(cands.len() - 1);
+                let w = cdf[pick] - if pick > 0 { cdf[pick - 1] } else { 0.0 };
+                let pdf = w / sum_w;
+                if pdf > 0.0 {
+                    total_light += lamp_contribution(lamps.lamp(cands[pick]), hit_pos, normal) / (pdf * n as f32);
+                }
+            }
+        }),
+        _ => {
+            for &id in cands {
+                total_light += lamp_contribution(lamps.lamp(id), hit_pos, normal);
             }
         }
     }
@@ -56,35 +74,56 @@ fn calculate_lamp_light(scene: &SceneRT, hit_pos: Vector3, normal: Vector3) -> V
     )
 }
 
-pub fn shade_block(pre: &CamPre, scene: &SceneRT, hit: &Hit, kind: BlockKind) -> Vector3 {
-    let (base_lin, alpha) = sample_block_linear_alpha(&scene.mats, hit.uv, hit.face, kind, scene.is_night);
+#[inline]
+fn lamp_attenuation(lamp: &Lamp, distance: f32) -> f32 {
+    if distance < lamp.range && distance > 0.1 {
+        1.0 / (1.0 + 0.5 * distance + 0.1 * distance * distance)
+    } else {
+        0.0
+    }
+}
 
-    let n = hit.n.normalized();
-    let l = (scene.light_pos - hit.p).normalized();
-    let v = (pre.eye - hit.p).normalized();
+/// Peso para el muestreo estocástico (aporte sin el término de la normal)
+#[inline]
+fn lamp_weight(lamp: &Lamp, hit_pos: Vector3) -> f32 {
+    lamp.intensity * lamp_attenuation(lamp, (lamp.pos - hit_pos).length())
+}
 
-    // Difuso suave (sin especular)
-    let k_wrap = 0.25;
-    let diff = clamp01((n.dot(l) + k_wrap) / (1.0 + k_wrap));
+#[inline]
+fn lamp_contribution(lamp: &Lamp, hit_pos: Vector3, normal: Vector3) -> Vector3 {
+    let to_lamp = lamp.pos - hit_pos;
+    let distance = to_lamp.length();
+    let attenuation = lamp_attenuation(lamp, distance);
+    if attenuation <= 0.0 { return Vector3::new(0.0, 0.0, 0.0); }
+    let dot_factor = normal.dot(to_lamp / distance).max(0.0);
+    lamp.color * (lamp.intensity * attenuation * dot_factor)
+}
 
-    let in_shadow = shadow_query_fast(scene, hit.p, n);
+pub fn shade_block(frame: &Frame, scene: &SceneRT, hit: &Hit, kind: BlockKind, smp: &mut dyn Sampler) -> Vector3 {
+    let pre = &frame.pre;
+    let (base_lin, alpha) = sample_surface(scene, hit.surface, hit.uv, hit.face, kind, hit.lod);
+
+    let n = hit.n.normalized();
+    let v = (pre.eye - hit.p).normalized();
 
     // Iluminación diferente para día y noche
-    let (ambient, sun_intensity) = if scene.is_night {
-        (0.03, 0.1)  // Muy poca luz ambiental y del sol durante la noche
+    let ambient = if scene.is_night {
+        0.03  // Muy poca luz ambiental durante la noche
     } else {
-        (0.12, 1.0)  // Iluminación normal durante el día
+        0.12  // Iluminación normal durante el día
     };
     
-    let mut c_lin = base_lin * ambient;
-    if !in_shadow { c_lin += base_lin * (diff * sun_intensity); }
+    let ao = ambient_occlusion(frame, scene, hit, smp);
+    let mut c_lin = base_lin * (ambient * ao);
+    c_lin += base_lin * direct_light(frame, scene, hit.p, n, smp);
     
     // Agregar luz de lámparas (solo de noche)
-    let lamp_light = calculate_lamp_light(scene, hit.p, n);
+    let lamp_light = calculate_lamp_light(frame, scene, hit.p, n, smp);
     c_lin += base_lin * lamp_light;
+    if let Some(e) = surface_emission(scene, hit.surface) { c_lin += e; }
 
     match kind {
-        BlockKind::Leaves | BlockKind::Water => {
+        k if matches!(k, BlockKind::Leaves | BlockKind::Water) || k.is_plant() => {
             // transparencia base (look fancy)
             let dir = (hit.p - pre.eye).normalized();
             let bg = sky_srgb(dir, scene.is_night);
@@ -100,7 +139,7 @@ pub fn shade_block(pre: &CamPre, scene: &SceneRT, hit: &Hit, kind: BlockKind) ->
                 let refl_srgb = match scene.water_mode {
                     WaterMode::Off => Vector3::new(0.0,0.0,0.0),
                     WaterMode::SkyOnly => sky_srgb(r, scene.is_night),
-                    WaterMode::ReflectOnce => trace_reflect_once(pre, scene, hit.p, r),
+                    WaterMode::ReflectOnce => trace_reflect_once(frame, scene, hit.p, r, smp),
                 };
                 return c_srgb * (1.0 - kr) + refl_srgb * kr;
             }
@@ -110,164 +149,79 @@ pub fn shade_block(pre: &CamPre, scene: &SceneRT, hit: &Hit, kind: BlockKind) ->
     }
 }
 
-fn trace_reflect_once(pre:&CamPre, scene:&SceneRT, origin:Vector3, dir:Vector3) -> Vector3 {
-    // Usa el mismo DDA del renderer construyendo una grid ligera aquí.
-    // Para no duplicar lógica, llamamos a una versión simplificada local.
-    if let Some((hit, kind)) = first_hit_fast(scene, origin, dir) {
-        match kind {
+fn trace_reflect_once(frame:&Frame, scene:&SceneRT, origin:Vector3, dir:Vector3, smp: &mut dyn Sampler) -> Vector3 {
+    // Usa el mismo DDA (y la misma grid) del renderer
+    if let Some(hh) = frame.trace(scene, origin, dir) {
+        match hh.kind {
             BlockKind::Water => sky_srgb(dir, scene.is_night),
-            k => shade_block(pre, scene, &hit, k),
+            k => shade_block(frame, scene, &hh.to_hit(), k, smp),
         }
     } else { sky_srgb(dir, scene.is_night) }
 }
 
-// ===== Sombra y primer impacto: versión "rápida" basada en rejilla =====
+// ===== Sombras =====
 
-// Construye grid ligero en cada llamada (barato vs ray tracing)
-#[derive(Clone)]
-struct GridLite { w:i32,h:i32,d:i32, min:Vector3, data:Vec<u8> }
-#[inline] fn kind_to_u8(k: BlockKind) -> u8 {
-    match k { BlockKind::Grass=>1, BlockKind::Dirt=>2, BlockKind::Stone=>3,
-              BlockKind::Log=>4, BlockKind::Leaves=>5, BlockKind::Water=>6, BlockKind::Lamp=>7 }
-}
-#[inline] fn u8_to_kind(v: u8) -> Option<BlockKind> {
-    match v {1=>Some(BlockKind::Grass),2=>Some(BlockKind::Dirt),3=>Some(BlockKind::Stone),
-             4=>Some(BlockKind::Log),5=>Some(BlockKind::Leaves),6=>Some(BlockKind::Water),7=>Some(BlockKind::Lamp),_=>None}
-}
-#[inline] fn gidx(g:&GridLite,x:i32,y:i32,z:i32)->usize {
-    (y as usize)*(g.w as usize)*(g.d as usize) + (z as usize)*(g.w as usize) + (x as usize)
-}
-fn build_grid_lite(scene:&SceneRT)->GridLite{
-    let mut miny=f32::INFINITY; let mut maxy=-f32::INFINITY;
-    for b in &scene.blocks{ miny=miny.min(b.center.y); maxy=maxy.max(b.center.y); }
-    let y0=(miny-0.5).floor()+0.5; let y1=(maxy+0.5).ceil()-0.5;
-    let h=(y1-y0+1.0).max(1.0) as i32;
-    let w=16i32; let d=16i32;
-    let min = Vector3::new(-(w as f32)*0.5, y0-0.5, -(d as f32)*0.5);
-    let mut data=vec![0u8;(w*d*h) as usize];
-    for b in &scene.blocks {
-        let ix=((b.center.x + (w as f32)*0.5).floor() as i32).clamp(0,w-1);
-        let iy=((b.center.y - y0).round() as i32).clamp(0,h-1);
-        let iz=((b.center.z + (d as f32)*0.5).floor() as i32).clamp(0,d-1);
-        data[gidx(&GridLite{w,h,d,min,data:Vec::new()},ix,iy,iz)] = kind_to_u8(b.kind);
+/// Suma de todas las luces de la escena (difuso suave + sombra).
+/// Luces con radio (o de área) se muestrean `scene.shadow_samples` veces,
+/// en una rejilla desplazada por el sampler, para penumbra suave.
+fn direct_light(frame: &Frame, scene: &SceneRT, p: Vector3, n: Vector3, smp: &mut dyn Sampler) -> Vector3 {
+    // El sol casi no alumbra de noche; las demás luces sí
+    let sun_intensity = if scene.is_night { 0.1 } else { 1.0 };
+    let k_wrap = 0.25;
+    // umbral de hojas: dither por texel rotado con cada muestra (converge a alpha)
+    let leaf_shift = if frame.sample == 0 { 0.0 } else { rand01(hash_u32(frame.sample)) };
+
+    let mut sum = Vector3::new(0.0, 0.0, 0.0);
+    for light in &scene.lights {
+        let ns = if light.is_soft() { scene.shadow_samples.max(1) } else { 1 };
+        // estratos sx × sy sobre la luz, todos corridos por el mismo punto 2D
+        let sx = (ns as f32).sqrt().ceil() as u32;
+        let sy = ns.div_ceil(sx);
+        let j = if light.is_soft() { smp.next_2d() } else { [0.5, 0.5] };
+        let mut acc = Vector3::new(0.0, 0.0, 0.0);
+        for s in 0..ns {
+            let u = [((s % sx) as f32 + j[0]) / sx as f32, ((s / sx) as f32 + j[1]) / sy as f32];
+            let Some(ls) = light.sample(p, u) else { continue; };
+            // Difuso suave (sin especular)
+            let diff = clamp01((n.dot(ls.wi) + k_wrap) / (1.0 + k_wrap));
+            if diff <= 0.0 { continue; }
+            if shadow_query(frame, scene, p + n*1e-3, ls.wi, ls.dist, leaf_shift) { continue; }
+            acc += ls.le * diff;
+        }
+        let k = if let LightKind::Directional { .. } = light.kind { sun_intensity } else { 1.0 };
+        sum += acc * (k / ns as f32);
     }
-    GridLite{w,h,d,min,data}
-}
-#[inline]
-fn ray_aabb(o:Vector3, d:Vector3, mn:Vector3, mx:Vector3)->Option<(f32,f32)>{
-    let inv=Vector3::new(1.0/d.x,1.0/d.y,1.0/d.z);
-    let mut t0=(mn-o)*inv; let mut t1=(mx-o)*inv;
-    if t0.x>t1.x{std::mem::swap(&mut t0.x,&mut t1.x);}
-    if t0.y>t1.y{std::mem::swap(&mut t0.y,&mut t1.y);}
-    if t0.z>t1.z{std::mem::swap(&mut t0.z,&mut t1.z);}
-    let tmin=t0.x.max(t0.y.max(t0.z)); let tmax=t1.x.min(t1.y.min(t1.z));
-    if tmax<tmin || tmax<=1e-4 {None} else {Some((tmin.max(1e-4),tmax))}
+    sum
 }
 
-fn first_hit_fast(scene:&SceneRT, o:Vector3, d:Vector3) -> Option<(Hit, BlockKind)> {
-    let g = build_grid_lite(scene);
-    let max = g.min + Vector3::new(g.w as f32, g.h as f32, g.d as f32);
-    let (mut t, tmax_all) = ray_aabb(o,d,g.min,max)?;
-    let mut p = o + d*t;
-
-    let mut ix=((p.x-g.min.x).floor() as i32).clamp(0,g.w-1);
-    let mut iy=((p.y-g.min.y).floor() as i32).clamp(0,g.h-1);
-    let mut iz=((p.z-g.min.z).floor() as i32).clamp(0,g.d-1);
-
-    let (sx,sy,sz)=(if d.x>0.0{1}else{-1}, if d.y>0.0{1}else{-1}, if d.z>0.0{1}else{-1});
-
-    let next_x = g.min.x + (if d.x>0.0 {(ix+1) as f32} else {ix as f32});
-    let next_y = g.min.y + (if d.y>0.0 {(iy+1) as f32} else {iy as f32});
-    let next_z = g.min.z + (if d.z>0.0 {(iz+1) as f32} else {iz as f32});
-
-    let mut tmaxx= if d.x!=0.0 {(next_x-o.x)/d.x} else {f32::INFINITY};
-    let mut tmaxy= if d.y!=0.0 {(next_y-o.y)/d.y} else {f32::INFINITY};
-    let mut tmaxz= if d.z!=0.0 {(next_z-o.z)/d.z} else {f32::INFINITY};
-    let tdx= if d.x!=0.0 {(1.0/d.x).abs()} else {f32::INFINITY};
-    let tdy= if d.y!=0.0 {(1.0/d.y).abs()} else {f32::INFINITY};
-    let tdz= if d.z!=0.0 {(1.0/d.z).abs()} else {f32::INFINITY};
-
-    let mut face:u8=255;
-    while t<=tmax_all {
-        if ix>=0 && ix<g.w && iy>=0 && iy<g.h && iz>=0 && iz<g.d {
-            let v=g.data[gidx(&g,ix,iy,iz)];
-            if let Some(kind)=u8_to_kind(v){
-                p=o+d*t;
-                // normal/uv
-                let n = match face {
-                    0=>Vector3::new(-1.0,0.0,0.0), 1=>Vector3::new(1.0,0.0,0.0),
-                    2=>Vector3::new(0.0,-1.0,0.0), 3=>Vector3::new(0.0,1.0,0.0),
-                    4=>Vector3::new(0.0,0.0,-1.0), 5=>Vector3::new(0.0,0.0,1.0),
-                    _=>Vector3::new(0.0,1.0,0.0)
-                };
-                let cx=g.min.x+ix as f32+0.5; let cy=g.min.y+iy as f32+0.5; let cz=g.min.z+iz as f32+0.5;
-                let s=Vector3::new(p.x-(cx-0.5), p.y-(cy-0.5), p.z-(cz-0.5));
-                let uv= match face {
-                    0=>[1.0 - s.z, 1.0 - s.y],
-                    1=>[s.z,       1.0 - s.y],
-                    2=>[s.x,       s.z      ],
-                    3=>[s.x,       1.0 - s.z],
-                    4=>[s.x,       1.0 - s.y],
-                    5=>[1.0 - s.x, 1.0 - s.y],
-                    _=>[0.0,0.0]
-                };
-
-                if matches!(kind, BlockKind::Leaves) {
-                    let (_c,a)=sample_block_linear_alpha(&scene.mats,uv,face,kind,scene.is_night);
-                    if a < 0.1 { /* continúa */ } else {
-                        let hit=Hit{id:1,t,p,n,uv,face}; return Some((hit,kind));
-                    }
-                } else {
-                    let hit=Hit{id:1,t,p,n,uv,face}; return Some((hit,kind));
-                }
-            }
-        }
-        if tmaxx <= tmaxy && tmaxx <= tmaxz {
-            t=tmaxx; tmaxx+=tdx; ix+=sx; face= if sx==1 {0} else {1};
-        } else if tmaxy <= tmaxz {
-            t=tmaxy; tmaxy+=tdy; iy+=sy; face= if sy==1 {2} else {3};
-        } else {
-            t=tmaxz; tmaxz+=tdz; iz+=sz; face= if sz==1 {4} else {5};
-        }
-    }
-    None
+/// ¿Hay algo entre `p` y la luz (dirección `d`, a distancia `dist_l`)?
+/// `leaf_shift` desplaza el dither de las hojas entre muestras.
+fn shadow_query(frame:&Frame, scene:&SceneRT, p:Vector3, d:Vector3, dist_l:f32, leaf_shift:f32) -> bool {
+    frame.trace(scene, p, d).is_some_and(|hit| blocks_light(scene, &hit, dist_l, leaf_shift))
 }
 
-pub fn shadow_query_fast(scene:&SceneRT, p:Vector3, n:Vector3) -> bool {
-    let eps=1e-3;
-    let to_light = scene.light_pos - p;
-    let dist_l = to_light.length();
-    let d = to_light / dist_l;
-    if let Some((hit, kind)) = first_hit_fast(scene, p + n*eps, d) {
-        if hit.t < dist_l {
-            return match kind {
-                BlockKind::Leaves => {
-                    // dither estable por texel para penumbra
-                    let (_c,a)=sample_block_linear_alpha(&scene.mats, hit.uv, hit.face, kind, scene.is_night);
-                    let m = hash01(hit.uv[0]*64.0, hit.uv[1]*64.0, hit.face as f32);
-                    a > m
-                }
-                BlockKind::Water => false,
-                _ => true
-            };
+/// ¿El impacto de un rayo de sombra tapa la luz que está a `dist_l`?
+#[inline]
+fn blocks_light(scene:&SceneRT, hit:&DdaHit, dist_l:f32, leaf_shift:f32) -> bool {
+    if hit.t >= dist_l || !casts_shadow(scene, hit.surface) { return false; }
+    match hit.kind {
+        k if k == BlockKind::Leaves || k.is_plant() => {
+            // dither estable por texel para penumbra
+            let (_c,a)=sample_block_linear_alpha(&scene.mats, hit.uv, hit.face, hit.kind, scene.is_night, hit.lod);
+            let texel = hash_u32((hit.uv[0]*64.0) as u32 ^ hash_u32((hit.uv[1]*64.0) as u32 ^ hash_u32(hit.face as u32)));
+            let m = (rand01(texel) + leaf_shift).fract();
+            a > m
         }
+        BlockKind::Water => false,
+        _ => true
     }
-    false
 }
 
-pub fn shade_floor(_pre: &CamPre, scene: &SceneRT, hit: &Hit) -> Vector3 {
+pub fn shade_floor(frame: &Frame, scene: &SceneRT, hit: &Hit, smp: &mut dyn Sampler) -> Vector3 {
     let n = hit.n.normalized();
-    let l = (scene.light_pos - hit.p).normalized();
-
-    let k_wrap = 0.25;
-    let diff = clamp01((n.dot(l) + k_wrap) / (1.0 + k_wrap));
-
-    let in_shadow = shadow_query_fast(scene, hit.p, n);
 
     let ambient = 0.10;
     let mut c_lin = scene.floor_color * ambient;
-    if !in_shadow {
-        c_lin += scene.floor_color * diff;
-    }
+    c_lin += scene.floor_color * direct_light(frame, scene, hit.p, n, smp);
     gamma_encode(c_lin)
 }
@@ -0,0 +1,346 @@
+use std::fs;
+use std::sync::Arc;
+
+use raylib::core::math::Vector3;
+
+use crate::geometry::Surface;
+use crate::world::BlockKind;
+
+use super::color::srgb_to_linear;
+use super::grid::DdaHit;
+
+/// Pasos máximos al trazar una forma por su distancia
+const SDF_STEPS: u32 = 128;
+/// Distancia a la superficie que ya cuenta como impacto
+const SDF_EPS: f32 = 1e-4;
+
+/// Distancia con signo a la superficie (negativa adentro), desde el centro de la forma
+pub type SdfFn = Arc<dyn Fn(Vector3) -> f32 + Send + Sync>;
+
+/// Geometría de una forma, centrada en su `center` y con el eje en Y
+#[derive(Clone)]
+pub enum ShapeKind {
+    Sphere { radius: f32 },
+    Cylinder { radius: f32, half_height: f32 }, // con tapas
+    Torus { major: f32, minor: f32 },          // anillo en el plano XZ
+    Sdf { f: SdfFn, bound: f32 },              // todo cabe en la esfera de radio `bound`
+}
+
+/// Material de una forma: color difuso y cuánto brilla por sí misma
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeMaterial {
+    pub color: Vector3, // lineal
+    pub glow: f32,      // emite color · glow (0 = no brilla)
+}
+
+#[derive(Clone)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub center: Vector3,
+    pub mat: ShapeMaterial,
+}
+
+impl Shape {
+    /// Esfera que brilla con `color`, para marcar un punto (p.ej. dónde está una luz)
+    pub fn marker(center: Vector3, radius: f32, color: Vector3) -> Self {
+        Self { kind: ShapeKind::Sphere { radius }, center, mat: ShapeMaterial { color, glow: 4.0 } }
+    }
+
+    /// `t` del primer punto de la forma en el rayo (`t` > 1e-4) y su normal
+    fn intersect(&self, o: Vector3, d: Vector3) -> Option<(f32, Vector3)> {
+        let q = o - self.center;
+        match &self.kind {
+            ShapeKind::Sphere { radius } => {
+                let (a, b, c) = (d.dot(d), q.dot(d), q.dot(q) - radius * radius);
+                let t = first_root(a, b, c)?;
+                Some((t, (q + d * t) / *radius))
+            }
+            ShapeKind::Cylinder { radius, half_height: h } => cylinder(q, d, *radius, *h),
+            ShapeKind::Torus { major, minor } => torus(q, d, *major, *minor),
+            ShapeKind::Sdf { f, bound } => sphere_trace(q, d, f.as_ref(), *bound),
+        }
+    }
+}
+
+/// Formas sueltas de la escena (esferas, cilindros, toros y superficies dadas por su
+/// distancia), en las coordenadas de los bloques del mundo. Son pocas, así que cada
+/// rayo las prueba todas; lo que brilla no tapa la luz (una esfera puesta sobre una
+/// luz para verla no la apaga).
+#[derive(Clone, Default)]
+pub struct Shapes {
+    list: Vec<Shape>,
+}
+
+impl Shapes {
+    pub fn new(list: Vec<Shape>) -> Self { Self { list } }
+
+    pub fn is_empty(&self) -> bool { self.list.is_empty() }
+
+    pub fn len(&self) -> usize { self.list.len() }
+
+    /// Estas formas y además `extra`
+    pub fn with(&self, extra: impl IntoIterator<Item = Shape>) -> Self {
+        Self { list: self.list.iter().cloned().chain(extra).collect() }
+    }
+
+    /// Primera forma antes de `t_max`, con el rayo en las celdas del mundo
+    pub fn trace(&self, o: Vector3, d: Vector3, t_max: f32) -> Option<DdaHit> {
+        let mut best = None;
+        let mut t_best = t_max;
+        for (i, s) in self.list.iter().enumerate() {
+            if let Some((t, n)) = s.intersect(o, d) && t < t_best {
+                t_best = t;
+                best = Some((i, n));
+            }
+        }
+        let (i, n) = best?;
+        let n = if n.dot(d) > 0.0 { -n } else { n }; // desde adentro: la cara que mira al rayo
+        // `kind` no se usa para las formas: cuenta como un bloque opaco
+        Some(DdaHit {
+            t: t_best, p: o + d * t_best, face: 255, n, uv: [0.0, 0.0], kind: BlockKind::Stone,
+            lod: 0, inst: None, surface: Surface::Shape(i as u32),
+        })
+    }
+
+    /// Lo más cercano entre `hit` y las formas
+    #[inline]
+    pub fn nearest(&self, o: Vector3, d: Vector3, hit: Option<DdaHit>) -> Option<DdaHit> {
+        if self.is_empty() { return hit; }
+        self.trace(o, d, hit.map_or(f32::INFINITY, |h| h.t)).or(hit)
+    }
+
+    /// Color lineal y alpha de la forma `i`
+    #[inline]
+    pub fn sample(&self, i: u32) -> (Vector3, f32) {
+        (self.list[i as usize].mat.color, 1.0)
+    }
+
+    /// Luz que emite la forma `i` (lineal)
+    #[inline]
+    pub fn emission(&self, i: u32) -> Vector3 {
+        let m = &self.list[i as usize].mat;
+        m.color * m.glow
+    }
+
+    /// ¿La forma `i` brilla? (entonces no hace sombra)
+    #[inline]
+    pub fn glows(&self, i: u32) -> bool {
+        self.list[i as usize].mat.glow > 0.0
+    }
+
+    /// Lee formas de un archivo (opcional). Formato, una por línea, con el centro en
+    /// coordenadas de capa como assets/instances.txt (columna, altura en capas y fila,
+    /// con decimales), las medidas en bloques y el color en sRGB 0..1:
+    ///   esfera   x y z  radio                     r g b [brillo]
+    ///   cilindro x y z  radio alto                r g b [brillo]
+    ///   toro     x y z  radio_mayor radio_menor   r g b [brillo]
+    ///   caja     x y z  ancho alto fondo borde    r g b [brillo]  (por su distancia)
+    ///   capsula  x y z  radio alto                r g b [brillo]  (por su distancia)
+    pub fn load(path: &str, grid_w: usize, grid_h: usize) -> Self {
+        let txt = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(_) => return Self::default(), // el archivo es opcional
+        };
+        let shift = Vector3::new(0.5 - grid_w as f32 * 0.5, 0.0, 0.5 - grid_h as f32 * 0.5);
+        let mut list = Vec::new();
+        for (ln, line) in txt.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            match parse_shape_line(line) {
+                Some(mut s) => {
+                    s.center += shift;
+                    list.push(s);
+                }
+                None => eprintln!("WARN: {path}:{} línea de forma inválida: {line}", ln + 1),
+            }
+        }
+        Self::new(list)
+    }
+}
+
+fn parse_shape_line(line: &str) -> Option<Shape> {
+    let tok: Vec<&str> = line.split_whitespace().collect();
+    let nums: Vec<f32> = tok.get(1..)?.iter().map(|t| t.parse().ok()).collect::<Option<_>>()?;
+    let sizes = match *tok.first()? { "esfera" => 1, "cilindro" | "toro" | "capsula" => 2, "caja" => 4, _ => return None };
+    if !(sizes + 6..=sizes + 7).contains(&nums.len()) { return None; }
+    let (center, m, rest) = (Vector3::new(nums[0], nums[1], nums[2]), &nums[3..3 + sizes], &nums[3 + sizes..]);
+    if m.iter().any(|&v| v <= 0.0) { return None; }
+    let kind = match tok[0] {
+        "esfera" => ShapeKind::Sphere { radius: m[0] },
+        "cilindro" => ShapeKind::Cylinder { radius: m[0], half_height: m[1] * 0.5 },
+        "toro" => ShapeKind::Torus { major: m[0], minor: m[1] },
+        "caja" => rounded_box(Vector3::new(m[0], m[1], m[2]) * 0.5, m[3]),
+        _ => capsule(m[0], m[1] * 0.5),
+    };
+    let color = srgb_to_linear(Vector3::new(rest[0], rest[1], rest[2]));
+    Some(Shape { kind, center, mat: ShapeMaterial { color, glow: rest.get(3).copied().unwrap_or(0.0).max(0.0) } })
+}
+
+/// Caja de medios lados `half` con las aristas redondeadas con radio `r`
+pub fn rounded_box(half: Vector3, r: f32) -> ShapeKind {
+    let r = r.min(half.x).min(half.y).min(half.z);
+    let inner = half - Vector3::new(r, r, r);
+    ShapeKind::Sdf {
+        f: Arc::new(move |p: Vector3| {
+            let q = Vector3::new(p.x.abs(), p.y.abs(), p.z.abs()) - inner;
+            Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length() + q.x.max(q.y).max(q.z).min(0.0) - r
+        }),
+        bound: half.length(),
+    }
+}
+
+/// Cilindro de radio `r` con puntas redondas, con el eje de -`half_height` a `half_height`
+pub fn capsule(r: f32, half_height: f32) -> ShapeKind {
+    ShapeKind::Sdf {
+        f: Arc::new(move |p: Vector3| Vector3::new(p.x, p.y - p.y.clamp(-half_height, half_height), p.z).length() - r),
+        bound: half_height + r,
+    }
+}
+
+/// Menor raíz t > 1e-4 de a·t² + 2b·t + c
+#[inline]
+fn first_root(a: f32, b: f32, c: f32) -> Option<f32> {
+    let h = b * b - a * c;
+    if h < 0.0 { return None; }
+    let h = h.sqrt();
+    [(-b - h) / a, (-b + h) / a].into_iter().find(|&t| t > 1e-4)
+}
+
+/// Cilindro de eje Y con tapas en ±h (rayo desde el centro)
+fn cylinder(q: Vector3, d: Vector3, r: f32, h: f32) -> Option<(f32, Vector3)> {
+    let mut best: Option<(f32, Vector3)> = None;
+    let mut keep = |t: f32, n: Vector3| if t > 1e-4 && best.is_none_or(|(b, _)| t < b) { best = Some((t, n)); };
+    // costado: solo la parte entre las tapas
+    let (a, b, c) = (d.x * d.x + d.z * d.z, q.x * d.x + q.z * d.z, q.x * q.x + q.z * q.z - r * r);
+    if a > 1e-12 && b * b - a * c >= 0.0 {
+        let s = (b * b - a * c).sqrt();
+        for t in [(-b - s) / a, (-b + s) / a] {
+            let p = q + d * t;
+            if p.y.abs() <= h { keep(t, Vector3::new(p.x, 0.0, p.z) / r); }
+        }
+    }
+    // tapas: dentro del círculo
+    if d.y.abs() > 1e-12 {
+        for y in [-h, h] {
+            let t = (y - q.y) / d.y;
+            let p = q + d * t;
+            if p.x * p.x + p.z * p.z <= r * r { keep(t, Vector3::new(0.0, y.signum(), 0.0)); }
+        }
+    }
+    best
+}
+
+/// Toro de eje Y: los puntos a `minor` del círculo de radio `major` en XZ. Se resuelve
+/// la cuártica (|p|² + R² - r²)² = 4R²(x² + z²) a lo largo del rayo (en f64, desde
+/// donde entra a su esfera para no perder precisión)
+fn torus(q: Vector3, d: Vector3, major: f32, minor: f32) -> Option<(f32, Vector3)> {
+    let len = d.length();
+    let dn = d / len;
+    // esfera que lo envuelve
+    let (b, c) = (q.dot(dn), q.dot(q) - (major + minor) * (major + minor));
+    if b * b - c < 0.0 { return None; }
+    let t0 = (-b - (b * b - c).sqrt()).max(0.0);
+
+    let o = q + dn * t0;
+    let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+    let (dx, dy, dz) = (dn.x as f64, dn.y as f64, dn.z as f64);
+    let (rr, r2) = ((major as f64).powi(2), (minor as f64).powi(2));
+    let n = ox * dx + oy * dy + oz * dz;
+    let k = ox * ox + oy * oy + oz * oz + rr - r2;
+    let coef = [
+        4.0 * n,
+        4.0 * n * n + 2.0 * k - 4.0 * rr * (dx * dx + dz * dz),
+        4.0 * n * k - 8.0 * rr * (ox * dx + oz * dz),
+        k * k - 4.0 * rr * (ox * ox + oz * oz),
+    ];
+    let s = solve_quartic(coef).into_iter().filter(|&s| t0 as f64 + s > 1e-4 * len as f64)
+        .fold(f64::INFINITY, f64::min);
+    if !s.is_finite() { return None; }
+
+    let p = o + dn * s as f32;
+    // normal: desde el punto más cercano del círculo central
+    let ring = Vector3::new(p.x, 0.0, p.z);
+    let ring = if ring.length() > 1e-6 { ring.normalized() * major } else { ring };
+    Some(((t0 + s as f32) / len, (p - ring).normalized()))
+}
+
+/// Raíces reales de t⁴ + a·t³ + b·t² + c·t + e (Ferrari), pulidas con Newton
+fn solve_quartic([a, b, c, e]: [f64; 4]) -> Vec<f64> {
+    // t = y - a/4 deja y⁴ + p·y² + q·y + r
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = e - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    let mut quad = |s: f64, k: f64| { // raíces de y² + s·y + k
+        let h = s * s - 4.0 * k;
+        if h >= 0.0 { ys.extend([(-s - h.sqrt()) * 0.5, (-s + h.sqrt()) * 0.5]); }
+    };
+    if q.abs() < 1e-12 {
+        // bicuadrada: y² es raíz de z² + p·z + r
+        let h = p * p - 4.0 * r;
+        if h >= 0.0 {
+            for z in [(-p - h.sqrt()) * 0.5, (-p + h.sqrt()) * 0.5] {
+                if z >= 0.0 { quad(0.0, -z); }
+            }
+        }
+    } else {
+        // m > 0 de la cúbica resolvente parte la cuártica en dos cuadráticas
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 { return Vec::new(); }
+        let s = (2.0 * m).sqrt();
+        quad(s, (p + 2.0 * m) / 2.0 - q / (2.0 * s));
+        quad(-s, (p + 2.0 * m) / 2.0 + q / (2.0 * s));
+    }
+    let f = |t: f64| (((t + a) * t + b) * t + c) * t + e;
+    let df = |t: f64| ((4.0 * t + 3.0 * a) * t + 2.0 * b) * t + c;
+    ys.into_iter().map(|y| {
+        let mut t = y - a / 4.0;
+        for _ in 0..2 {
+            let g = df(t);
+            if g.abs() > 1e-12 { t -= f(t) / g; }
+        }
+        t
+    }).collect()
+}
+
+/// La mayor raíz real de x³ + a·x² + b·x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        let th = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let root = |k: f64| -2.0 * q.sqrt() * ((th + k * std::f64::consts::TAU) / 3.0).cos() - a / 3.0;
+        root(0.0).max(root(1.0)).max(root(-1.0))
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let t = if s != 0.0 { q / s } else { 0.0 };
+        s + t - a / 3.0
+    }
+}
+
+/// Avanza por el rayo lo que diga la distancia hasta tocar la superficie (dentro de la
+/// esfera de radio `bound`); la normal sale del gradiente de la distancia
+fn sphere_trace(q: Vector3, d: Vector3, f: &(dyn Fn(Vector3) -> f32 + Send + Sync), bound: f32) -> Option<(f32, Vector3)> {
+    let len = d.length();
+    let dn = d / len;
+    let (b, c) = (q.dot(dn), q.dot(q) - bound * bound);
+    if b * b - c < 0.0 { return None; }
+    let h = (b * b - c).sqrt();
+    let (mut t, t1) = ((-b - h).max(1e-4 * len), -b + h);
+    for _ in 0..SDF_STEPS {
+        if t > t1 { return None; }
+        let p = q + dn * t;
+        let dist = f(p);
+        if dist.abs() < SDF_EPS {
+            // gradiente por cuatro puntos en tetraedro
+            let e = 1e-3;
+            let ks = [Vector3::new(1.0, -1.0, -1.0), Vector3::new(-1.0, -1.0, 1.0), Vector3::new(-1.0, 1.0, -1.0), Vector3::new(1.0, 1.0, 1.0)];
+            let n = ks.iter().fold(Vector3::zero(), |acc, &k| acc + k * f(p + k * e));
+            return Some((t / len, n.normalized()));
+        }
+        t += dist.abs();
+    }
+    None
+}
@@ -0,0 +1,244 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+
+use super::SceneRT;
+use super::progressive::Progressive;
+use super::scaler::ResScaler;
+
+/// Sin cambios por este tiempo se considera que la vista se asentó y se pasa a resolución completa
+const SETTLE: Duration = Duration::from_millis(150);
+
+/// Qué cambió desde el último trabajo que se mandó
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    Camera, // solo la cámara: se reproyecta lo anterior
+    Scene,  // cualquier otra cosa: se empieza de cero
+}
+
+/// Opciones del render que elige la ventana
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub denoise: bool,
+    pub temporal: bool,                // reproyectar al mover la cámara
+    pub frame_budget: Option<Duration>, // resolución dinámica: tiempo por frame al interactuar
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { denoise: false, temporal: true, frame_budget: Some(Duration::from_millis(33)) }
+    }
+}
+
+/// Escena a renderizar y cómo
+struct Job {
+    scene: SceneRT,
+    w: u32, h: u32,
+    change: Change,
+    settings: RenderSettings,
+}
+
+/// La imagen que el hilo de render va dejando para la ventana
+struct Output {
+    img: RgbaImage,
+    samples: u32,
+    active: f32,      // fracción de pixeles que todavía reciben muestras
+    size: (u32, u32), // resolución interna con la que salió
+    version: u64,     // sube cada vez que cambia algo de la imagen
+}
+
+struct Shared {
+    job: Mutex<Option<Job>>, // el último trabajo pendiente (uno nuevo pisa al anterior)
+    wake: Condvar,
+    cancel: Arc<AtomicBool>,
+    quit: AtomicBool,
+    out: Mutex<Output>,
+}
+
+/// Hilo de render en segundo plano. La ventana manda la escena cuando algo cambia
+/// y cada frame recoge la imagen más nueva (terminada o a medias), así nunca se traba.
+/// Mientras nada cambie el hilo sigue acumulando muestras hasta converger.
+pub struct RenderWorker {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+    seen: u64,
+}
+
+impl RenderWorker {
+    pub fn spawn() -> Self {
+        let shared = Arc::new(Shared {
+            job: Mutex::new(None),
+            wake: Condvar::new(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            quit: AtomicBool::new(false),
+            out: Mutex::new(Output { img: RgbaImage::new(1, 1), samples: 0, active: 1.0, size: (1, 1), version: 0 }),
+        });
+        let sh = Arc::clone(&shared);
+        let handle = thread::Builder::new()
+            .name("render".into())
+            .spawn(move || worker_loop(&sh))
+            .expect("No pude crear el hilo de render");
+        Self { shared, handle: Some(handle), seen: 0 }
+    }
+
+    /// Manda una escena nueva. Si se estaba refinando la anterior, se cancela.
+    pub fn submit(&self, scene: &SceneRT, w: u32, h: u32, change: Change, settings: RenderSettings) {
+        let mut job = self.shared.job.lock().unwrap();
+        // si ya había uno pendiente, el cambio que manda es el más fuerte de los dos
+        let change = job.as_ref().map_or(change, |j| j.change.max(change));
+        *job = Some(Job { scene: scene.clone(), w, h, change, settings });
+        self.shared.cancel.store(true, Ordering::Relaxed);
+        self.shared.wake.notify_one();
+    }
+
+    /// La imagen más nueva, solo si cambió desde la última vez que se pidió
+    pub fn latest(&mut self) -> Option<RgbaImage> {
+        let out = self.shared.out.lock().unwrap();
+        if out.version == self.seen { return None; }
+        self.seen = out.version;
+        Some(out.img.clone())
+    }
+
+    /// Muestras acumuladas en la imagen publicada
+    pub fn samples(&self) -> u32 {
+        self.shared.out.lock().unwrap().samples
+    }
+
+    /// Fracción de los pixeles que siguen recibiendo muestras (muestreo adaptativo)
+    pub fn active_fraction(&self) -> f32 {
+        self.shared.out.lock().unwrap().active
+    }
+
+    /// Resolución interna de la imagen publicada (menor que la final mientras se interactúa)
+    pub fn render_size(&self) -> (u32, u32) {
+        self.shared.out.lock().unwrap().size
+    }
+}
+
+impl Drop for RenderWorker {
+    fn drop(&mut self) {
+        self.shared.quit.store(true, Ordering::Relaxed);
+        self.shared.cancel.store(true, Ordering::Relaxed);
+        self.shared.wake.notify_one();
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+/// Publica la imagen llevándola al tamaño final `full` si salió más chica
+fn publish(sh: &Shared, img: &RgbaImage, samples: u32, active: f32, full: (u32, u32)) {
+    let size = img.dimensions();
+    let scaled = (size != full).then(|| imageops::resize(img, full.0, full.1, FilterType::Triangle));
+    let mut out = sh.out.lock().unwrap();
+    out.img.clone_from(scaled.as_ref().unwrap_or(img));
+    out.samples = samples;
+    out.active = active;
+    out.size = size;
+    out.version += 1;
+}
+
+fn worker_loop(sh: &Arc<Shared>) {
+    let mut progressive = Progressive::new(1, 1);
+    progressive.cancel = Some(Arc::clone(&sh.cancel));
+    // la primera imagen de cada vista se va mostrando por franjas
+    let sh2 = Arc::clone(sh);
+    progressive.preview = Some(Arc::new(move |y0: u32, band: &RgbaImage| {
+        let mut out = sh2.out.lock().unwrap();
+        if out.img.width() != band.width() { return; }
+        for (x, y, p) in band.enumerate_pixels() {
+            if y0 + y < out.img.height() { out.img.put_pixel(x, y0 + y, *p); }
+        }
+        out.version += 1;
+    }));
+    let mut scene: Option<SceneRT> = None;
+    let mut full = (1, 1);
+    let mut settings = RenderSettings::default();
+    let mut scaler = ResScaler::new();
+    let mut last_job = Instant::now();
+
+    loop {
+        // esperar trabajo nuevo, salvo que quede por acumular en la escena actual;
+        // si la imagen es de interacción (chica o reproyectada) se espera a que la vista se asiente
+        let job = {
+            let mut job = sh.job.lock().unwrap();
+            loop {
+                if job.is_some() || sh.quit.load(Ordering::Relaxed) { break; }
+                let Some(s) = scene.as_ref() else { job = sh.wake.wait(job).unwrap(); continue; };
+                let interactive = progressive.size() != full || progressive.provisional();
+                if settings.frame_budget.is_some() && interactive {
+                    let wait = SETTLE.saturating_sub(last_job.elapsed());
+                    if wait.is_zero() { break; }
+                    job = sh.wake.wait_timeout(job, wait).unwrap().0;
+                } else if progressive.done(s) {
+                    job = sh.wake.wait(job).unwrap();
+                } else {
+                    break;
+                }
+            }
+            if sh.quit.load(Ordering::Relaxed) { return; }
+            let j = job.take();
+            if j.is_some() { sh.cancel.store(false, Ordering::Relaxed); }
+            j
+        };
+
+        if let Some(job) = job {
+            last_job = Instant::now();
+            settings = job.settings;
+            progressive.denoise = settings.denoise;
+            progressive.temporal = settings.temporal;
+            progressive.cancel_first = false;
+            full = (job.w, job.h);
+            let s = scene.insert(job.scene);
+            {
+                // que la imagen publicada tenga el tamaño final antes de las franjas
+                let mut out = sh.out.lock().unwrap();
+                if out.img.dimensions() != full { out.img = RgbaImage::new(full.0, full.1); }
+            }
+
+            // con presupuesto de tiempo, la imagen de interacción sale a resolución reducida
+            // (y con menos rayos de sombra si ni así alcanza)
+            let (w, h) = if settings.frame_budget.is_some() { scaler.size(full.0, full.1) } else { full };
+            let shadows = scaler.shadow_samples(s.shadow_samples);
+            let quick;
+            let s: &SceneRT = if settings.frame_budget.is_some() && shadows != s.shadow_samples {
+                quick = SceneRT { shadow_samples: shadows, ..s.clone() };
+                &quick
+            } else {
+                s
+            };
+            let t0 = Instant::now();
+            let img = match job.change {
+                Change::Camera => progressive.camera_moved(s, w, h),
+                Change::Scene => {
+                    progressive.reset(w, h);
+                    progressive.accumulate(s); // primera muestra: no se cancela
+                    progressive.resolve()
+                }
+            };
+            publish(sh, img, 1, 1.0, full);
+            // solo los frames trazados enteros dicen cuánto cuesta la escala actual
+            let traced_all = !progressive.provisional();
+            if let Some(budget) = settings.frame_budget && traced_all {
+                scaler.update(t0.elapsed(), budget);
+            }
+            continue;
+        }
+
+        let Some(s) = scene.as_ref() else { continue; };
+        if progressive.size() != full || progressive.provisional() {
+            // la vista se asentó: a resolución completa; esa primera muestra sí se puede
+            // cortar, porque en pantalla ya está la de interacción
+            progressive.reset(full.0, full.1);
+            progressive.cancel_first = true;
+        }
+        if progressive.accumulate(s) {
+            let (n, active) = (progressive.samples(), progressive.active_fraction());
+            publish(sh, progressive.resolve(), n, active, full);
+        }
+    }
+}
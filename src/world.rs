@@ -1,16 +1,96 @@
-use std::{fs, path::Path};
+use std::{fs, path::Path, sync::OnceLock};
 use raylib::core::math::Vector3;
 use image::RgbaImage;
 
+use crate::geometry::WorldXform;
+
 // ----------- Tipos de bloque / material -----------
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum BlockKind { Grass, Dirt, Stone, Log, Leaves, Water, Lamp }
+pub enum BlockKind {
+    Grass, Dirt, Stone, Log, Leaves, Water, Lamp,
+    // no llenan su celda: se arman con cajas (ver raytracer/boxes.rs)
+    Slab, Stairs, Fence, Pane,
+    // plantas: dos quads en cruz (ver raytracer/plants.rs)
+    TallGrass, Flower, Sapling,
+}
 
 #[derive(Clone, Debug)]
 pub struct Block {
     pub center: Vector3,
     pub half: f32,       // 0.5 típico
     pub kind: BlockKind,
+    pub light: Option<LampParams>, // si emite luz (lámparas) y con qué parámetros
+}
+
+// ----------- Parámetros de luz por lámpara -----------
+/// Animación de la intensidad de una lámpara en el tiempo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LampAnim {
+    Steady,
+    Flicker { speed: f32, amount: f32 }, // antorcha: ruido suave
+    Pulse { speed: f32, amount: f32 },   // latido senoidal (speed en Hz)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LampParams {
+    pub color: Vector3,   // lineal 0..1
+    pub intensity: f32,
+    pub range: f32,
+    pub anim: LampAnim,
+}
+
+impl LampParams {
+    /// Intensidad en el instante `time`; `seed` desfasa lámparas iguales
+    pub fn intensity_at(&self, time: f32, seed: f32) -> f32 {
+        let k = match self.anim {
+            LampAnim::Steady => 1.0,
+            LampAnim::Flicker { speed, amount } => {
+                // value noise 1D interpolado (suave pero irregular)
+                let x = time * speed + seed * 17.0;
+                let (i, f) = (x.floor(), x.fract());
+                let f = f * f * (3.0 - 2.0 * f);
+                let n = noise1(i) * (1.0 - f) + noise1(i + 1.0) * f;
+                1.0 - amount * n
+            }
+            LampAnim::Pulse { speed, amount } => {
+                let s = (std::f32::consts::TAU * (time * speed + seed)).sin();
+                1.0 - amount * (0.5 + 0.5 * s)
+            }
+        };
+        self.intensity * k.max(0.0)
+    }
+}
+
+#[inline]
+fn noise1(i: f32) -> f32 {
+    let h = (i * 127.1).sin() * 43_758.547;
+    h - h.floor()
+}
+
+impl BlockKind {
+    /// Registro de luz por tipo: qué bloques emiten por defecto
+    pub fn light(self) -> Option<LampParams> {
+        match self {
+            // Color cálido de lámpara (naranja/amarillo)
+            BlockKind::Lamp => Some(LampParams {
+                color: Vector3::new(1.0, 0.8, 0.4),
+                intensity: 3.0,
+                range: 8.0,
+                anim: LampAnim::Steady,
+            }),
+            _ => None,
+        }
+    }
+
+    /// ¿Llena su celda entera? (las losas, escaleras, cercas, ventanas y plantas no)
+    pub fn is_cube(self) -> bool {
+        !matches!(self, BlockKind::Slab | BlockKind::Stairs | BlockKind::Fence | BlockKind::Pane) && !self.is_plant()
+    }
+
+    /// ¿Es una planta? (se recorta por alpha como las hojas)
+    pub fn is_plant(self) -> bool {
+        matches!(self, BlockKind::TallGrass | BlockKind::Flower | BlockKind::Sapling)
+    }
 }
 
 // Todas las texturas necesarias (las cargas en main.rs)
@@ -26,91 +106,94 @@ pub struct Materials {
     pub water: RgbaImage,
     pub lamp_off: RgbaImage,  // nueva textura
     pub lamp_on: RgbaImage,   // nueva textura
+    pub pane: RgbaImage,      // marco de ventana (el vidrio es transparente)
+    pub tall_grass: RgbaImage, // plantas (con alpha)
+    pub flower: RgbaImage,
+    pub sapling: RgbaImage,
+    /// Color y alpha promedio de cada textura, para los niveles de detalle
+    /// (se calculan solos la primera vez que se piden: dejar en `Default::default()`)
+    pub averages: OnceLock<Vec<(Vector3, f32)>>,
 }
 
-// ----------- Loader de capas 16x16 ---------------
+// ----------- Loader de capas ---------------
 // Lee assets/layers/layer_00.txt, layer_01.txt, ... hasta que falte uno.
-// Construye una grilla 16x16xL y devuelve **solo bloques de superficie**.
+// Construye una grilla grid_w x grid_h x L y devuelve **solo bloques de superficie**.
+// Las capas se leen de a una y solo se guardan tres a la vez (la de abajo, la
+// actual y la de arriba), así que un mapa grande no se carga entero en memoria.
 pub fn load_layers_dir(dir: &str, prefix: &str, grid_w: usize, grid_h: usize) -> Vec<Block> {
-    let mut layers: Vec<Vec<Vec<Option<BlockKind>>>> = Vec::new(); // [y][z][x]
-
-    // 1) Leer capas y normalizar a 16x16
-    let mut y = 0usize;
-    loop {
-        let fname = format!("{prefix}{:02}.txt", y);
-        let path = Path::new(dir).join(&fname);
-        if !path.exists() {
-            if y == 0 {
-                eprintln!("WARN: No se encontró ninguna capa en {dir}/ (esperaba {fname})");
-            }
-            break;
-        }
-        let txt = match fs::read_to_string(&path) {
-            Ok(s) => s,
-            Err(e) => { eprintln!("No pude leer {}: {e}", path.display()); break; }
-        };
+    // capa y como [z][x] aplanado; None si falta el archivo o no se pudo leer
+    let read = |y: usize| -> Option<Vec<Option<BlockKind>>> {
+        let txt = read_layer(dir, prefix, y)?;
         let raw = normalize_to_grid(&txt, grid_w, grid_h);
-        // map a Option<BlockKind>
-        let mut layer: Vec<Vec<Option<BlockKind>>> = vec![vec![None; grid_w]; grid_h];
-        for z in 0..grid_h {
-            for x in 0..grid_w {
-                layer[z][x] = char_to_kind(raw[z][x]);
-            }
-        }
-        layers.push(layer);
-        y += 1;
-    }
-
-    if layers.is_empty() { return Vec::new(); }
-
-    // 2) Extraer SOLO bloques de superficie (si alguna de sus 6 caras da a aire / borde)
-    let lw = grid_w as i32;
-    let lh = layers.len() as i32;
-    let ld = grid_h as i32;
+        Some(raw.iter().flatten().map(|&c| char_to_kind(c)).collect())
+    };
+    let Some(mut cur) = read(0) else {
+        eprintln!("WARN: No se encontró ninguna capa en {dir}/ (esperaba {prefix}00.txt)");
+        return Vec::new();
+    };
 
+    // Extraer SOLO bloques de superficie (si alguna de sus 6 caras da a aire / borde)
+    let (lw, ld) = (grid_w as i32, grid_h as i32);
+    let mut below: Option<Vec<Option<BlockKind>>> = None;
     let mut blocks = Vec::new();
-    for yy in 0..lh {
+    for yy in 0.. {
+        let above = read(yy + 1);
+        let at = |layer: &[Option<BlockKind>], x: i32, z: i32| layer[(z * lw + x) as usize];
         for zz in 0..ld {
             for xx in 0..lw {
-                let kind = layers[yy as usize][zz as usize][xx as usize];
-                if kind.is_none() { continue; }
-                let k = kind.unwrap();
-
-                let neigh = [
-                    (xx-1, yy,   zz),
-                    (xx+1, yy,   zz),
-                    (xx,   yy-1, zz),
-                    (xx,   yy+1, zz),
-                    (xx,   yy,   zz-1),
-                    (xx,   yy,   zz+1),
-                ];
-                let mut exposed = false;
-                for (nx, ny, nz) in neigh {
-                    if nx < 0 || nx >= lw || ny < 0 || ny >= lh || nz < 0 || nz >= ld {
-                        exposed = true; break;
-                    }
-                    if layers[ny as usize][nz as usize][nx as usize].is_none() {
-                        exposed = true; break;
-                    }
-                }
+                let Some(k) = at(&cur, xx, zz) else { continue; };
+
+                let side = [(xx-1, zz), (xx+1, zz), (xx, zz-1), (xx, zz+1)];
+                let exposed = side.iter().any(|&(nx, nz)| nx < 0 || nx >= lw || nz < 0 || nz >= ld || at(&cur, nx, nz).is_none())
+                    || [&below, &above].iter().any(|l| l.as_ref().is_none_or(|l| at(l, xx, zz).is_none()));
                 if !exposed { continue; }
 
                 // Centro del bloque; grid centrado en X/Z, Y desde 0 hacia arriba
                 let cx = (xx as f32 + 0.5) - grid_w as f32 * 0.5;
-                let cy = (yy as f32 + 0.5);
+                let cy = yy as f32 + 0.5;
                 let cz = (zz as f32 + 0.5) - grid_h as f32 * 0.5;
                 blocks.push(Block {
                     center: Vector3::new(cx, cy, cz),
                     half: 0.5,
                     kind: k,
+                    light: k.light(),
                 });
             }
         }
+        let Some(next) = above else { break; };
+        below = Some(std::mem::replace(&mut cur, next));
     }
     blocks
 }
 
-// ------------------- Normalización 16x16 -------------------
+/// Texto de la capa `y` (None si no existe o no se pudo leer)
+fn read_layer(dir: &str, prefix: &str, y: usize) -> Option<String> {
+    let path = Path::new(dir).join(format!("{prefix}{:02}.txt", y));
+    if !path.exists() { return None; }
+    match fs::read_to_string(&path) {
+        Ok(s) => Some(s),
+        Err(e) => { eprintln!("No pude leer {}: {e}", path.display()); None }
+    }
+}
+
+/// Tamaño de las capas de `dir` (ancho = hasta el último bloque de la fila más larga,
+/// fondo = hasta la última fila con bloques), como mínimo 16x16 y redondeado a par
+/// para que los centros de los bloques caigan a media celda como en 16x16. Las capas
+/// más chicas se rellenan con aire.
+pub fn layers_size(dir: &str, prefix: &str) -> (usize, usize) {
+    let (mut w, mut h) = (16, 16);
+    for txt in (0..).map_while(|y| read_layer(dir, prefix, y)) {
+        let rows = txt.lines().filter(|l| !l.trim_start().starts_with('#'));
+        for (z, row) in rows.enumerate() {
+            let syms = row.chars().filter(|&c| is_valid_symbol(c));
+            let Some(last) = syms.enumerate().filter(|&(_, c)| char_to_kind(c).is_some()).last() else { continue; };
+            (w, h) = (w.max(last.0 + 1), h.max(z + 1));
+        }
+    }
+    (w.next_multiple_of(2), h.next_multiple_of(2))
+}
+
+// ------------------- Normalización al tamaño de la grilla -------------------
 fn normalize_to_grid(txt: &str, grid_w: usize, grid_h: usize) -> Vec<Vec<char>> {
     let mut rows: Vec<Vec<char>> = Vec::with_capacity(grid_h);
 
@@ -132,7 +215,7 @@ fn normalize_to_grid(txt: &str, grid_w: usize, grid_h: usize) -> Vec<Vec<char>>
 }
 
 fn is_valid_symbol(c: char) -> bool {
-    matches!(c, 'g'|'G'|'d'|'D'|'s'|'S'|'l'|'L'|'v'|'V'|'w'|'W'|'p'|'P'|'.'|' ')
+    matches!(c, 'g'|'G'|'d'|'D'|'s'|'S'|'l'|'L'|'v'|'V'|'w'|'W'|'p'|'P'|'h'|'H'|'e'|'E'|'f'|'F'|'c'|'C'|'y'|'Y'|'r'|'R'|'a'|'A'|'.'|' ')
 }
 
 fn char_to_kind(c: char) -> Option<BlockKind> {
@@ -144,21 +227,217 @@ fn char_to_kind(c: char) -> Option<BlockKind> {
         'v' | 'V' => Some(BlockKind::Leaves),
         'w' | 'W' => Some(BlockKind::Water),
         'p' | 'P' => Some(BlockKind::Lamp),  // P de "Post" o "Pillar"
+        'h' | 'H' => Some(BlockKind::Slab),  // H de "half": media altura
+        'e' | 'E' => Some(BlockKind::Stairs), // E de escalera
+        'f' | 'F' => Some(BlockKind::Fence),
+        'c' | 'C' => Some(BlockKind::Pane),  // C de cristal
+        'y' | 'Y' => Some(BlockKind::TallGrass), // Y de yerba
+        'r' | 'R' => Some(BlockKind::Flower), // R de rosa
+        'a' | 'A' => Some(BlockKind::Sapling), // A de arbolito
         '.' | ' ' => None,
         _ => None,
     }
 }
 
-/// Mueve todos los bloques en Y (positivo = arriba, negativo = abajo).
-pub fn translate_blocks_y(blocks: &mut [Block], dy: f32) {
-    for b in blocks {
-        b.center.y += dy;
+// ----------- Overrides de luz por posición ---------------
+// Formato (una lámpara por línea, coordenadas de capa: x=columna, y=capa, z=fila):
+//   x y z  r g b  intensidad rango  [flicker vel amp | pulse vel amp]  [# comentario]
+// Se aplica sobre los bloques recién cargados (antes de moverlos).
+pub fn load_lamp_overrides(path: &str, grid_w: usize, grid_h: usize, blocks: &mut [Block]) {
+    let txt = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(_) => return, // el archivo es opcional
+    };
+    for (ln, line) in txt.lines().enumerate() {
+        // lo que va después de un '#' es comentario, también al final de la línea
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() { continue; }
+        let Some((x, y, z, params)) = parse_lamp_line(line) else {
+            eprintln!("WARN: {path}:{} línea de lámpara inválida: {line}", ln + 1);
+            continue;
+        };
+        let c = Vector3::new(
+            (x as f32 + 0.5) - grid_w as f32 * 0.5,
+            y as f32 + 0.5,
+            (z as f32 + 0.5) - grid_h as f32 * 0.5,
+        );
+        match blocks.iter_mut().find(|b| (b.center - c).length() < 1e-3) {
+            Some(b) => b.light = Some(params),
+            None => eprintln!("WARN: {path}:{} no hay bloque visible en ({x},{y},{z})", ln + 1),
+        }
     }
 }
 
-/// Estima un radio mínimo para que la luz no atraviese el mundo.
-pub fn suggest_min_light_radius(grid_w: usize, grid_h: usize, blocks: &[Block]) -> f32 {
+fn parse_lamp_line(line: &str) -> Option<(i32, i32, i32, LampParams)> {
+    let tok: Vec<&str> = line.split_whitespace().collect();
+    // 8 campos, u 11 con la animación; nada más después
+    if tok.len() != 8 && tok.len() != 11 { return None; }
+    let f = |i: usize| tok.get(i).and_then(|t| t.parse::<f32>().ok());
+    let (x, y, z) = (tok[0].parse().ok()?, tok[1].parse().ok()?, tok[2].parse().ok()?);
+    let anim = match tok.get(8).copied() {
+        None => LampAnim::Steady,
+        Some("flicker") => LampAnim::Flicker { speed: f(9)?, amount: f(10)? },
+        Some("pulse")   => LampAnim::Pulse   { speed: f(9)?, amount: f(10)? },
+        Some(_) => return None,
+    };
+    let (intensity, range) = (f(6)?, f(7)?);
+    if !intensity.is_finite() || !range.is_finite() || intensity < 0.0 || range <= 0.0 { return None; }
+    Some((x, y, z, LampParams {
+        color: Vector3::new(f(3)?, f(4)?, f(5)?),
+        intensity,
+        range,
+        anim,
+    }))
+}
+
+// ----------- Copias de modelos ---------------
+/// Una copia de un modelo puesta en el mundo (en coordenadas de los bloques del mundo)
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub model: String, // carpeta en assets/models/
+    pub pos: Vector3,  // dónde queda el centro de la base del modelo
+    pub yaw: f32,      // radianes
+    pub scale: f32,
+}
+
+// Formato (una copia por línea, posición en coordenadas de capa como assets/lamps.txt:
+// x=columna, y=capa, z=fila; con decimales vale quedar entre celdas):
+//   modelo  x y z  [giro_grados] [escala]
+// La base del modelo queda centrada en la celda (x, z) y apoyada en el piso de la capa y.
+pub fn load_placements(path: &str, grid_w: usize, grid_h: usize) -> Vec<Placement> {
+    let txt = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(_) => return Vec::new(), // el archivo es opcional
+    };
+    let mut out = Vec::new();
+    for (ln, line) in txt.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let Some(mut p) = parse_placement_line(line) else {
+            eprintln!("WARN: {path}:{} línea de copia inválida: {line}", ln + 1);
+            continue;
+        };
+        p.pos += Vector3::new(0.5 - grid_w as f32 * 0.5, 0.0, 0.5 - grid_h as f32 * 0.5);
+        out.push(p);
+    }
+    out
+}
+
+fn parse_placement_line(line: &str) -> Option<Placement> {
+    let tok: Vec<&str> = line.split_whitespace().collect();
+    if !(4..=6).contains(&tok.len()) { return None; }
+    let f = |i: usize| tok[i].parse::<f32>().ok();
+    let yaw = if tok.len() > 4 { f(4)? } else { 0.0 };
+    let scale = if tok.len() > 5 { f(5)? } else { 1.0 };
+    if scale <= 0.0 { return None; }
+    Some(Placement { model: tok[0].to_string(), pos: Vector3::new(f(1)?, f(2)?, f(3)?), yaw: yaw.to_radians(), scale })
+}
+
+/// Bloques de un modelo: capas `layer_00.txt`, ... en `dir`, igual que el mundo
+pub fn load_model(dir: &str) -> Vec<Block> {
+    let (w, h) = layers_size(dir, "layer_");
+    load_layers_dir(dir, "layer_", w, h)
+}
+
+/// ¿Hay lámparas animadas? (hay que re-renderizar cada frame de noche)
+pub fn has_animated_lamps(blocks: &[Block]) -> bool {
+    blocks.iter().any(|b| matches!(b.light, Some(p) if p.anim != LampAnim::Steady))
+}
+
+/// Posición inicial del mundo: sin girar ni escalar y subido/bajado para que el
+/// techo de la isla quede en y ≈ 1.2 (los bloques no se mueven, ver `WorldXform`)
+pub fn home_xform(blocks: &[Block]) -> WorldXform {
+    let maxy = blocks.iter().fold(-f32::INFINITY, |m, b| m.max(b.center.y));
+    let dy = if maxy.is_finite() { 1.2 - maxy } else { 0.0 };
+    WorldXform { offset: Vector3::new(0.0, dy, 0.0), ..Default::default() }
+}
+
+/// Estima un radio mínimo para que la luz no atraviese el mundo (puesto con `xf`).
+pub fn suggest_min_light_radius(grid_w: usize, grid_h: usize, blocks: &[Block], xf: &WorldXform) -> f32 {
     let top_y = blocks.iter().fold(0.0_f32, |m, b| m.max(b.center.y + b.half));
-    let horiz = grid_w.max(grid_h) as f32 * 0.6;
+    let top_y = xf.offset.y + top_y * xf.scale;
+    let horiz = grid_w.max(grid_h) as f32 * 0.6 * xf.scale + xf.offset.x.hypot(xf.offset.z);
     horiz.max(top_y + 2.0)
 }
+
+// ----------- Isla generada (mundos grandes para pruebas) ---------------
+/// Ruido de valor en una celda entera (0..1), determinista por `seed`
+fn hash01(x: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h & 0xff_ffff) as f32 / 0x100_0000 as f32
+}
+
+/// Ruido de valor suavizado con `octaves` octavas (celda base de `cell` bloques)
+fn value_noise(x: f32, z: f32, cell: f32, octaves: u32, seed: u32) -> f32 {
+    let (mut sum, mut amp, mut norm, mut f) = (0.0, 1.0, 0.0, 1.0 / cell);
+    for o in 0..octaves {
+        let (px, pz) = (x * f, z * f);
+        let (ix, iz) = (px.floor() as i32, pz.floor() as i32);
+        let s = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, tz) = (s(px - ix as f32), s(pz - iz as f32));
+        let v = |dx, dz| hash01(ix + dx, iz + dz, seed.wrapping_add(o));
+        let a = v(0, 0) + (v(1, 0) - v(0, 0)) * tx;
+        let b = v(0, 1) + (v(1, 1) - v(0, 1)) * tx;
+        sum += amp * (a + (b - a) * tz);
+        norm += amp;
+        amp *= 0.5;
+        f *= 2.0;
+    }
+    sum / norm
+}
+
+/// Isla de `size`x`size` columnas (centrada en X/Z como las capas) con colinas,
+/// agua alrededor y algunos árboles. Igual que `load_layers_dir`, devuelve solo
+/// los bloques de superficie. Sirve para probar el trazado en mundos grandes.
+pub fn generate_island(size: usize, seed: u32) -> Vec<Block> {
+    const SEA: i32 = 3;
+    let n = size as i32;
+    // las islas grandes tienen montañas más altas (1024 columnas llegan a ~200 de alto)
+    let peak = (size as f32 / 5.0).max(20.0);
+    let height = |x: i32, z: i32| -> i32 {
+        if x < 0 || z < 0 || x >= n || z >= n { return 0; }
+        // más alto al centro y bajo el agua hacia el borde
+        let (u, v) = ((x as f32 + 0.5) / size as f32 * 2.0 - 1.0, (z as f32 + 0.5) / size as f32 * 2.0 - 1.0);
+        let fall = (1.0 - (u * u + v * v).sqrt()).max(0.0);
+        let hill = value_noise(x as f32, z as f32, 24.0, 4, seed);
+        (fall * (4.0 + hill * peak)) as i32
+    };
+    let center = |x: i32, y: i32, z: i32| Vector3::new(
+        (x as f32 + 0.5) - size as f32 * 0.5,
+        y as f32 + 0.5,
+        (z as f32 + 0.5) - size as f32 * 0.5,
+    );
+    let block = |c: Vector3, kind: BlockKind| Block { center: c, half: 0.5, kind, light: kind.light() };
+
+    let mut blocks = Vec::new();
+    for z in 0..n {
+        for x in 0..n {
+            let h = height(x, z);
+            // desde la columna vecina más baja hasta la cima (lo que se ve de lado)
+            let low = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                .map(|&(dx, dz)| height(x + dx, z + dz) + 1)
+                .fold(h, i32::min)
+                .max(0);
+            for y in low..=h {
+                let kind = if y < h { if h - y > 2 { BlockKind::Stone } else { BlockKind::Dirt } }
+                    else if h <= SEA { BlockKind::Dirt } else { BlockKind::Grass };
+                blocks.push(block(center(x, y, z), kind));
+            }
+            if h < SEA {
+                blocks.push(block(center(x, SEA, z), BlockKind::Water));
+            } else if h > SEA + 1 && x % 5 == 2 && z % 5 == 2 && hash01(x, z, seed ^ 0x7ee) < 0.3 {
+                // árbol: tronco de 3 y copa de 3x3x2
+                for y in h + 1..=h + 3 { blocks.push(block(center(x, y, z), BlockKind::Log)); }
+                for y in h + 3..=h + 4 {
+                    for (dx, dz) in (-1..=1).flat_map(|dx| (-1..=1).map(move |dz| (dx, dz))) {
+                        if (dx, dz) != (0, 0) || y == h + 4 { blocks.push(block(center(x + dx, y, z + dz), BlockKind::Leaves)); }
+                    }
+                }
+            }
+        }
+    }
+    blocks
+}